@@ -1,7 +1,11 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{Token, TokenAccount, Mint, MintTo, Burn},
+    token_2022::spl_token_2022::{
+        self,
+        extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+    },
+    token_interface::{self, TokenInterface, TokenAccount, Mint, MintTo, Burn, CloseAccount, TransferChecked},
 };
 
 // Constants for program configuration
@@ -29,6 +33,56 @@ pub mod constants {
     
     // Cooldown
     pub const DELEGATION_COOLDOWN_HOURS: u8 = 1;       // 1 hour
+    pub const MAX_DELEGATION_COOLDOWN_SECS: i64 = 7 * 24 * 60 * 60; // 7 days
+
+    // Destination allowlist
+    pub const MAX_ALLOWED_DESTINATIONS: usize = 8;
+
+    // Concurrent delegations per user
+    pub const MAX_DELEGATIONS_PER_USER: usize = 16;
+
+    // Locked savings lots per vault
+    pub const MAX_VAULT_TRANCHES: usize = 32;
+    pub const TRANCHE_BUCKET_SECS: i64 = 24 * 60 * 60;              // unlock times round up to a day
+
+    // Caller-supplied timestamp hints in auto_deduct
+    pub const MAX_TIMESTAMP_STALENESS_SECS: i64 = 120;
+    pub const MAX_CLOCK_SKEW_SECS: i64 = 30;
+
+    // Trade receipts
+    pub const TRADE_RECEIPT_RETENTION_SECS: i64 = 24 * 60 * 60;     // 24 hours
+
+    // Fee collection
+    pub const FEE_COLLECTION_COOLDOWN_SECS: i64 = 24 * 60 * 60;      // 24 hours
+    pub const MAX_FEE_COLLECTION_COOLDOWN_SECS: i64 = 30 * 24 * 60 * 60; // 30 days
+
+    // Pause flags, one bit per instruction family
+    pub const PAUSE_INITIALIZE_VAULT: u16 = 1 << 0;
+    pub const PAUSE_DELEGATE_FUNDS: u16 = 1 << 1;
+    pub const PAUSE_AUTO_DEDUCT: u16 = 1 << 2;
+    pub const PAUSE_UPDATE_VAULT: u16 = 1 << 3;
+    pub const PAUSE_WITHDRAW: u16 = 1 << 4;
+    pub const PAUSE_RENEW_SUBSCRIPTION: u16 = 1 << 5;
+    pub const PAUSE_REVOKE_DELEGATION: u16 = 1 << 6;
+    pub const PAUSE_ALL: u16 = (1 << 7) - 1;
+
+    // Governance
+    pub const MAX_GOVERNANCE_SIGNERS: usize = 10;
+
+    // Timelock
+    pub const TIMELOCK_DELAY_SECS: i64 = 2 * 24 * 60 * 60;           // 48 hours
+    pub const MIN_TIMELOCK_DELAY_SECS: i64 = 60 * 60;                // 1 hour
+    pub const MAX_TIMELOCK_DELAY_SECS: i64 = 30 * 24 * 60 * 60;      // 30 days
+    pub const TIMELOCK_GRACE_PERIOD_SECS: i64 = 14 * 24 * 60 * 60;  // 14 days
+    pub const MAX_SUBSCRIPTION_FEE_SOL: u64 = 10_000_000_000;        // 10 SOL
+    pub const DELEGATION_LIMIT_CAP_SOL: u64 = 1_000_000_000_000;     // 1000 SOL
+
+    // Global bounds for the limits stored in ProtocolConfig
+    pub const LOCK_DAYS_CAP: u8 = 180;
+    pub const SAVE_RATE_CAP: u8 = 50;
+    pub const FEE_RATE_CAP: u8 = 10;
+    pub const MAX_FEE_RATE_STEP: u8 = 1;                             // max change per update
+    pub const SUBSCRIPTION_PERIOD_DAYS_CAP: u8 = 90;
 }
 
 declare_id!("6ttMWaSxYvukX3dYJwuGCp7eaHWL6Fw28ZRhsULWMPp9");
@@ -44,26 +98,44 @@ pub mod savefi {
         mint_authority.bump = ctx.bumps.mint_authority;
 
         let fee_account = &mut ctx.accounts.fee_account;
-        fee_account.authority = ctx.accounts.admin.key();
         fee_account.balance = 0;
         fee_account.fee_rate = fee_rate;
+        fee_account.last_collection_time = 0;
+        fee_account.collection_cooldown = constants::FEE_COLLECTION_COOLDOWN_SECS;
+        fee_account.previous_fee_rate = fee_rate;
+        fee_account.fee_rate_updated_at = Clock::get()?.unix_timestamp;
 
         let config = &mut ctx.accounts.config;
         config.admin = ctx.accounts.admin.key();
-        config.paused = false;
+        config.pause_flags = 0;
+        config.pause_reason = 0;
+        config.pause_updated_at = Clock::get()?.unix_timestamp;
         config.save_token_mint = ctx.accounts.save_token_mint.key();
+        config.pending_admin = Pubkey::default();
+        config.multisig_enabled = false;
+        config.subscription_fee = constants::SUBSCRIPTION_FEE_SOL;
+        config.min_delegation = constants::MIN_DELEGATION_SOL;
+        config.max_delegation = constants::MAX_DELEGATION_SOL;
+        config.timelock_delay = constants::TIMELOCK_DELAY_SECS;
+        config.next_change_id = 0;
+        config.limits = ProtocolLimits::default();
+        config.admin_epoch = 0;
 
         let guard = &mut ctx.accounts.reentrancy_guard;
         guard.locked = false;
 
+        let reserve = &mut ctx.accounts.reserve;
+        reserve.total_saved = 0;
+        reserve.bump = ctx.bumps.reserve;
+
         Ok(())
     }
 
     pub fn initialize_vault(ctx: Context<InitializeVault>, savings_rate: u8, lock_days: u8) -> Result<()> {
         let config = &ctx.accounts.config;
-        require!(!config.paused, SaveFiError::ProtocolPaused);
-        require!(savings_rate >= constants::MIN_SAVE_RATE && savings_rate <= constants::MAX_SAVE_RATE, SaveFiError::InvalidSaveRate);
-        require!(lock_days >= constants::MIN_LOCK_DAYS && lock_days <= constants::MAX_LOCK_DAYS, SaveFiError::InvalidLockPeriod);
+        require!(!config.is_paused(constants::PAUSE_INITIALIZE_VAULT), SaveFiError::InitializeVaultPaused);
+        config.limits.check_save_rate(savings_rate)?;
+        config.limits.check_lock_days(lock_days)?;
         let vault = &mut ctx.accounts.vault;
         require!(vault.owner == Pubkey::default(), SaveFiError::VaultAlreadyInitialized);
 
@@ -72,8 +144,10 @@ pub mod savefi {
         vault.lock_period_days = lock_days;
         vault.balance = 0;
         vault.lock_until = 0;
+        vault.tranches = Vec::new();
+        vault.open_token_mints = 0;
         vault.is_active = true;
-        vault.next_payment_due = Clock::get()?.unix_timestamp + 7 * 24 * 60 * 60;
+        vault.next_payment_due = Clock::get()?.unix_timestamp + (config.limits.subscription_period_days as i64 * 24 * 60 * 60);
 
         let proxy = &mut ctx.accounts.proxy_account;
         proxy.owner = ctx.accounts.user.key();
@@ -82,14 +156,48 @@ pub mod savefi {
         Ok(())
     }
 
-    pub fn delegate_funds(ctx: Context<DelegateFunds>, amount: u64, lock_days: u8) -> Result<()> {
-        require!(amount >= constants::MIN_DELEGATION_SOL && amount <= constants::MAX_DELEGATION_SOL, SaveFiError::InvalidDelegationAmount);
-        require!(lock_days >= constants::MIN_LOCK_DAYS && lock_days <= constants::MAX_LOCK_DAYS, SaveFiError::InvalidLockPeriod);
+    pub fn delegate_funds(ctx: Context<DelegateFunds>, id: u8, amount: u64, lock_days: u8) -> Result<()> {
+        require!(!ctx.accounts.config.is_paused(constants::PAUSE_DELEGATE_FUNDS), SaveFiError::DelegateFundsPaused);
+        let config = &ctx.accounts.config;
+        require!((config.min_delegation..=config.max_delegation).contains(&amount), SaveFiError::InvalidDelegationAmount);
+        config.limits.check_lock_days(lock_days)?;
+        let registry = &mut ctx.accounts.delegation_registry;
+        registry.owner = ctx.accounts.user.key();
+        registry.bump = ctx.bumps.delegation_registry;
+        registry.add(id)?;
+
+        ctx.accounts.delegation.open(
+            ctx.accounts.user.key(),
+            id,
+            amount,
+            Clock::get()?.unix_timestamp + (lock_days as i64 * 24 * 60 * 60),
+            ctx.bumps.delegation,
+            Pubkey::default(),
+        );
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.user.to_account_info(),
+                    to: ctx.accounts.delegation.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        Ok(())
+    }
+
+    pub fn top_up_delegation(ctx: Context<TopUpDelegation>, amount: u64) -> Result<()> {
+        let config = &ctx.accounts.config;
+        require!(!config.is_paused(constants::PAUSE_DELEGATE_FUNDS), SaveFiError::DelegateFundsPaused);
         let delegation = &mut ctx.accounts.delegation;
-        delegation.owner = ctx.accounts.user.key();
-        delegation.delegated_amount = amount;
-        delegation.delegation_expiry = Clock::get()?.unix_timestamp + (lock_days as i64 * 24 * 60 * 60);
-        delegation.bump = ctx.bumps.delegation;
+        require!(delegation.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        let new_amount = delegation.delegated_amount.checked_add(amount).ok_or(SaveFiError::InvalidDelegationAmount)?;
+        require!(
+            amount > 0 && (config.min_delegation..=config.max_delegation).contains(&new_amount),
+            SaveFiError::InvalidDelegationAmount
+        );
+        delegation.delegated_amount = new_amount;
         anchor_lang::system_program::transfer(
             CpiContext::new(
                 ctx.accounts.system_program.to_account_info(),
@@ -103,32 +211,57 @@ pub mod savefi {
         Ok(())
     }
 
-    pub fn auto_deduct(ctx: Context<AutoDeduct>, trade_amount: u64, timestamp: i64) -> Result<()> {
+    /// Pushes the expiry out to `lock_days` from now; it can never move earlier.
+    pub fn extend_delegation(ctx: Context<ExtendDelegation>, lock_days: u8) -> Result<()> {
+        let config = &ctx.accounts.config;
+        require!(!config.is_paused(constants::PAUSE_DELEGATE_FUNDS), SaveFiError::DelegateFundsPaused);
+        config.limits.check_lock_days(lock_days)?;
+        let delegation = &mut ctx.accounts.delegation;
+        require!(delegation.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        let new_expiry = Clock::get()?.unix_timestamp + (lock_days as i64 * 24 * 60 * 60);
+        require!(new_expiry > delegation.delegation_expiry, SaveFiError::InvalidLockPeriod);
+        delegation.delegation_expiry = new_expiry;
+        Ok(())
+    }
+
+    pub fn auto_deduct(ctx: Context<AutoDeduct>, trade_id: [u8; 16], trade_amount: u64, timestamp: Option<i64>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        check_timestamp_hint(timestamp, now)?;
+        ctx.accounts.trade_receipt.record(
+            ctx.accounts.delegation.key(),
+            trade_id,
+            trade_amount,
+            now,
+            ctx.accounts.operator.key(),
+            ctx.bumps.trade_receipt,
+        )?;
+
         let config = &ctx.accounts.config;
         let guard = &mut ctx.accounts.reentrancy_guard;
-        require!(!config.paused, SaveFiError::ProtocolPaused);
+        require!(!config.is_paused(constants::PAUSE_AUTO_DEDUCT), SaveFiError::AutoDeductPaused);
         require!(!guard.locked, SaveFiError::ReentrancyDetected);
         
         // Get delegation amount first
         let delegated_amount = ctx.accounts.delegation.delegated_amount;
         require!(trade_amount > 0 && trade_amount <= delegated_amount, SaveFiError::InvalidSaveAmount);
         require!(ctx.accounts.vault.is_active, SaveFiError::VaultInactive);
-        require!(timestamp <= ctx.accounts.delegation.delegation_expiry, SaveFiError::DelegationExpired);
+        require!(now <= ctx.accounts.delegation.delegation_expiry, SaveFiError::DelegationExpired);
+        ctx.accounts.delegation.record_deduction(now)?;
+        ctx.accounts.delegation.record_spend(trade_amount, now, constants::DAILY_LIMIT_SOL)?;
+        ctx.accounts.delegation.check_destination(&ctx.accounts.destination)?;
+        // The seeds on `dex_program` already tie it to the destination's owner
+        ctx.accounts.delegation.check_dex_program(ctx.accounts.dex_program.as_deref())?;
         guard.locked = true;
 
         let vault = &mut ctx.accounts.vault;
         require!(vault.owner == ctx.accounts.delegation.owner, SaveFiError::Unauthorized);
 
         // Calculate savings amount
-        let save_amount = (trade_amount as u128)
-            .checked_mul(vault.savings_rate as u128)
-            .ok_or(SaveFiError::InvalidSaveAmount)?
-            .checked_div(100)
-            .ok_or(SaveFiError::InvalidSaveAmount)? as u64;
+        let save_amount = percent_of(trade_amount, vault.savings_rate)?;
 
         // Mint SaveSOL tokens
         if save_amount > 0 {
-            anchor_spl::token::mint_to(
+            token_interface::mint_to(
                 CpiContext::new_with_signer(
                     ctx.accounts.token_program.to_account_info(),
                     MintTo {
@@ -141,44 +274,31 @@ pub mod savefi {
                 save_amount,
             )?;
             vault.balance += save_amount;
-            vault.lock_until = timestamp + (vault.lock_period_days as i64 * 24 * 60 * 60);
+            let unlock_at = now + (vault.lock_period_days as i64 * 24 * 60 * 60);
+            vault.lock_until = vault.add_tranche(save_amount, unlock_at, now)?;
+
+            // Back the minted SaveSOL 1:1 with lamports held in the reserve
+            let reserve_info = ctx.accounts.reserve.to_account_info();
+            let delegation_info = ctx.accounts.delegation.to_account_info();
+            ctx.accounts.reserve.deposit(&reserve_info, &delegation_info, save_amount)?;
         }
 
         // Calculate and transfer fees
         let fee_account = &mut ctx.accounts.fee_account;
-        let fee_amount = (trade_amount as u128)
-            .checked_mul(fee_account.fee_rate as u128)
-            .ok_or(SaveFiError::InvalidSaveAmount)?
-            .checked_div(100)
-            .ok_or(SaveFiError::InvalidSaveAmount)? as u64;
+        let fee_amount = percent_of(trade_amount, fee_account.fee_rate)?;
 
+        // The delegation is program-owned, so lamports are moved directly
         if fee_amount > 0 {
             fee_account.balance += fee_amount;
-            anchor_lang::system_program::transfer(
-                CpiContext::new(
-                    ctx.accounts.system_program.to_account_info(),
-                    anchor_lang::system_program::Transfer {
-                        from: ctx.accounts.delegation.to_account_info(),
-                        to: ctx.accounts.fee_account.to_account_info(),
-                    },
-                ),
-                fee_amount,
-            )?;
+            ctx.accounts.delegation.sub_lamports(fee_amount)?;
+            ctx.accounts.fee_account.add_lamports(fee_amount)?;
         }
 
         // Transfer remaining amount to destination
         let remaining_amount = trade_amount.checked_sub(save_amount).unwrap().checked_sub(fee_amount).unwrap();
         if remaining_amount > 0 {
-            anchor_lang::system_program::transfer(
-                CpiContext::new(
-                    ctx.accounts.system_program.to_account_info(),
-                    anchor_lang::system_program::Transfer {
-                        from: ctx.accounts.delegation.to_account_info(),
-                        to: ctx.accounts.destination.to_account_info(),
-                    },
-                ),
-                remaining_amount,
-            )?;
+            ctx.accounts.delegation.sub_lamports(remaining_amount)?;
+            ctx.accounts.destination.add_lamports(remaining_amount)?;
         }
 
         // Update delegation amount after all transfers
@@ -188,11 +308,190 @@ pub mod savefi {
         Ok(())
     }
 
+    pub fn close_trade_receipt(ctx: Context<CloseTradeReceipt>) -> Result<()> {
+        ctx.accounts.trade_receipt.check_retention_elapsed(Clock::get()?.unix_timestamp)
+    }
+
+    /// Escrows `amount` of an SPL token in a token account owned by the delegation PDA.
+    pub fn delegate_tokens(ctx: Context<DelegateTokens>, id: u8, amount: u64, lock_days: u8) -> Result<()> {
+        let config = &ctx.accounts.config;
+        require!(!config.is_paused(constants::PAUSE_DELEGATE_FUNDS), SaveFiError::DelegateFundsPaused);
+        config.limits.check_lock_days(lock_days)?;
+        let mint_config = &ctx.accounts.mint_config;
+        require!(mint_config.enabled, SaveFiError::MintDisabled);
+        // Only what reaches the escrow after the mint's transfer fee is delegated
+        let credited = amount
+            .checked_sub(transfer_fee(&ctx.accounts.mint.to_account_info(), amount, Clock::get()?.epoch)?)
+            .ok_or(SaveFiError::InvalidDelegationAmount)?;
+        require!(
+            (mint_config.min_delegation..=mint_config.max_delegation).contains(&credited),
+            SaveFiError::InvalidDelegationAmount
+        );
+        let registry = &mut ctx.accounts.delegation_registry;
+        registry.owner = ctx.accounts.user.key();
+        registry.bump = ctx.bumps.delegation_registry;
+        registry.add(id)?;
+
+        let token_savings = &mut ctx.accounts.token_savings;
+        token_savings.vault = ctx.accounts.vault.key();
+        token_savings.mint = ctx.accounts.mint.key();
+        token_savings.bump = ctx.bumps.token_savings;
+
+        ctx.accounts.delegation.open(
+            ctx.accounts.user.key(),
+            id,
+            credited,
+            Clock::get()?.unix_timestamp + (lock_days as i64 * 24 * 60 * 60),
+            ctx.bumps.delegation,
+            ctx.accounts.mint.key(),
+        );
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.escrow.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+        Ok(())
+    }
+
+    /// Token counterpart of `auto_deduct`: splits the trade from the escrow into the
+    /// vault's token account, the fee account's token account and the destination.
+    pub fn auto_deduct_tokens(
+        ctx: Context<AutoDeductTokens>,
+        trade_id: [u8; 16],
+        trade_amount: u64,
+        timestamp: Option<i64>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        check_timestamp_hint(timestamp, now)?;
+        ctx.accounts.trade_receipt.record(
+            ctx.accounts.delegation.key(),
+            trade_id,
+            trade_amount,
+            now,
+            ctx.accounts.operator.key(),
+            ctx.bumps.trade_receipt,
+        )?;
+
+        require!(!ctx.accounts.config.is_paused(constants::PAUSE_AUTO_DEDUCT), SaveFiError::AutoDeductPaused);
+        let mint_config = &ctx.accounts.mint_config;
+        require!(mint_config.enabled, SaveFiError::MintDisabled);
+        let vault = &ctx.accounts.vault;
+        require!(vault.is_active, SaveFiError::VaultInactive);
+        let delegation = &mut ctx.accounts.delegation;
+        require!(trade_amount > 0 && trade_amount <= delegation.delegated_amount, SaveFiError::InvalidSaveAmount);
+        require!(now <= delegation.delegation_expiry, SaveFiError::DelegationExpired);
+        require!(!delegation.dex_only, SaveFiError::DexOnlyUnsupported);
+        delegation.record_deduction(now)?;
+        delegation.record_spend(trade_amount, now, mint_config.daily_limit)?;
+        delegation.check_token_destination(&ctx.accounts.destination)?;
+
+        // Savings and the protocol fee arrive in full; any transfer fee the mint
+        // withholds comes out of the destination's share
+        let mint_info = ctx.accounts.mint.to_account_info();
+        let save_amount = percent_of(trade_amount, vault.savings_rate)?;
+        let fee_amount = percent_of(trade_amount, ctx.accounts.fee_account.fee_rate)?;
+        let epoch = Clock::get()?.epoch;
+        let save_transfer = with_transfer_fee(&mint_info, save_amount, epoch)?;
+        let fee_transfer = with_transfer_fee(&mint_info, fee_amount, epoch)?;
+        let remaining_amount = trade_amount
+            .checked_sub(save_transfer)
+            .and_then(|amount| amount.checked_sub(fee_transfer))
+            .ok_or(SaveFiError::InvalidSaveAmount)?;
+        delegation.delegated_amount -= trade_amount;
+        if save_amount > 0 {
+            let unlock_at = now + (vault.lock_period_days as i64 * 24 * 60 * 60);
+            let token_savings = &mut ctx.accounts.token_savings;
+            if token_savings.balance == 0 {
+                ctx.accounts.vault.open_token_mints += 1;
+            }
+            token_savings.balance += save_amount;
+            token_savings.add_tranche(save_amount, unlock_at, now)?;
+        }
+
+        let accounts = &ctx.accounts;
+        accounts.pay_out(accounts.vault_token_account.to_account_info(), save_transfer)?;
+        accounts.pay_out(accounts.fee_token_account.to_account_info(), fee_transfer)?;
+        accounts.pay_out(accounts.destination.to_account_info(), remaining_amount)?;
+        Ok(())
+    }
+
+    /// Refunds everything left in the escrow to the owner and closes the escrow account.
+    pub fn revoke_token_delegation(ctx: Context<RevokeTokenDelegation>) -> Result<()> {
+        require!(!ctx.accounts.config.is_paused(constants::PAUSE_REVOKE_DELEGATION), SaveFiError::RevokeDelegationPaused);
+        let delegation = &mut ctx.accounts.delegation;
+        require!(delegation.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        delegation.delegated_amount = 0;
+        delegation.delegation_expiry = 0;
+
+        let user_key = ctx.accounts.user.key();
+        let signer_seeds: &[&[u8]] = &[b"delegation", user_key.as_ref(), &[delegation.id], &[delegation.bump]];
+        let escrow_amount = ctx.accounts.escrow.amount;
+        if escrow_amount > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.escrow.to_account_info(),
+                        mint: ctx.accounts.mint.to_account_info(),
+                        to: ctx.accounts.user_token_account.to_account_info(),
+                        authority: ctx.accounts.delegation.to_account_info(),
+                    },
+                    &[signer_seeds],
+                ),
+                escrow_amount,
+                ctx.accounts.mint.decimals,
+            )?;
+        }
+
+        close_escrow(
+            &ctx.accounts.token_program,
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.escrow.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.delegation.to_account_info(),
+            signer_seeds,
+        )
+    }
+
+    /// Releases `amount` of matured token savings for one mint, or all of them when `None`.
+    pub fn withdraw_saved_tokens(ctx: Context<WithdrawSavedTokens>, amount: Option<u64>) -> Result<()> {
+        require!(!ctx.accounts.config.is_paused(constants::PAUSE_WITHDRAW), SaveFiError::WithdrawPaused);
+        require!(ctx.accounts.vault.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        let amount = ctx.accounts.token_savings.withdraw_unlocked(amount, Clock::get()?.unix_timestamp)?;
+        if ctx.accounts.token_savings.balance == 0 {
+            ctx.accounts.vault.open_token_mints -= 1;
+        }
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.user_token_account.to_account_info(),
+                    authority: ctx.accounts.vault.to_account_info(),
+                },
+                &[&[b"vault", ctx.accounts.user.key().as_ref(), &[ctx.bumps.vault]]],
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+        Ok(())
+    }
+
     pub fn update_vault(ctx: Context<UpdateVault>, new_savings_rate: u8, new_lock_days: u8) -> Result<()> {
         let config = &ctx.accounts.config;
-        require!(!config.paused, SaveFiError::ProtocolPaused);
-        require!(new_savings_rate >= constants::MIN_SAVE_RATE && new_savings_rate <= constants::MAX_SAVE_RATE, SaveFiError::InvalidSaveRate);
-        require!(new_lock_days >= constants::MIN_LOCK_DAYS && new_lock_days <= constants::MAX_LOCK_DAYS, SaveFiError::InvalidLockPeriod);
+        require!(!config.is_paused(constants::PAUSE_UPDATE_VAULT), SaveFiError::UpdateVaultPaused);
+        config.limits.check_save_rate(new_savings_rate)?;
+        config.limits.check_lock_days(new_lock_days)?;
         let vault = &mut ctx.accounts.vault;
         require!(vault.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
         vault.savings_rate = new_savings_rate;
@@ -200,20 +499,16 @@ pub mod savefi {
         Ok(())
     }
 
-    pub fn withdraw(ctx: Context<Withdraw>) -> Result<()> {
+    /// Burns `amount` of the vault's SaveSOL, or all of the matured savings when `None`,
+    /// and redeems it 1:1 for lamports from the reserve.
+    pub fn withdraw(ctx: Context<Withdraw>, amount: Option<u64>) -> Result<()> {
         let config = &ctx.accounts.config;
-        require!(!config.paused, SaveFiError::ProtocolPaused);
+        require!(!config.is_paused(constants::PAUSE_WITHDRAW), SaveFiError::WithdrawPaused);
+        let now = Clock::get()?.unix_timestamp;
         let vault = &mut ctx.accounts.vault;
         require!(vault.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
-        require!(
-            Clock::get()?.unix_timestamp >= vault.lock_until,
-            SaveFiError::VaultLocked
-        );
-        let amount = vault.balance;
-        require!(amount > 0, SaveFiError::EmptyVault);
-
-        vault.balance = 0;
-        anchor_spl::token::burn(
+        let amount = vault.withdraw_unlocked(amount, now)?;
+        token_interface::burn(
             CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
                 Burn {
@@ -225,10 +520,32 @@ pub mod savefi {
             ),
             amount,
         )?;
-        Ok(())
+
+        let reserve_info = ctx.accounts.reserve.to_account_info();
+        let user_info = ctx.accounts.user.to_account_info();
+        ctx.accounts.reserve.redeem(&reserve_info, &user_info, amount)
+    }
+
+    /// Read-only summary of the vault's SaveSOL split into locked and matured savings.
+    pub fn vault_balances(ctx: Context<GetVaultBalances>) -> Result<VaultBalances> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &ctx.accounts.vault;
+        Ok(VaultBalances {
+            total: vault.balance,
+            locked: vault.locked_balance(now),
+            unlocked: vault.unlocked_balance(now),
+            next_unlock_at: vault
+                .tranches
+                .iter()
+                .map(|tranche| tranche.unlock_at)
+                .filter(|unlock_at| *unlock_at > now)
+                .min()
+                .unwrap_or(0),
+        })
     }
 
     pub fn renew_subscription(ctx: Context<RenewSubscription>) -> Result<()> {
+        require!(!ctx.accounts.config.is_paused(constants::PAUSE_RENEW_SUBSCRIPTION), SaveFiError::RenewSubscriptionPaused);
         let vault = &mut ctx.accounts.vault;
         require!(vault.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
         anchor_lang::system_program::transfer(
@@ -239,30 +556,23 @@ pub mod savefi {
                     to: ctx.accounts.fee_account.to_account_info(),
                 },
             ),
-            constants::SUBSCRIPTION_FEE_SOL,
+            ctx.accounts.config.subscription_fee,
         )?;
-        vault.next_payment_due = Clock::get()?.unix_timestamp + (constants::SUBSCRIPTION_PERIOD_DAYS as i64 * 24 * 60 * 60);
+        vault.next_payment_due = Clock::get()?.unix_timestamp + (ctx.accounts.config.limits.subscription_period_days as i64 * 24 * 60 * 60);
         vault.is_active = true;
         Ok(())
     }
 
     pub fn revoke_delegation(ctx: Context<RevokeDelegation>) -> Result<()> {
+        require!(!ctx.accounts.config.is_paused(constants::PAUSE_REVOKE_DELEGATION), SaveFiError::RevokeDelegationPaused);
         // Get delegation amount first
         let remaining_amount = ctx.accounts.delegation.delegated_amount;
         require!(ctx.accounts.delegation.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
 
-        // Transfer remaining amount if any
+        // Transfer remaining amount if any; the delegation is program-owned
         if remaining_amount > 0 {
-            anchor_lang::system_program::transfer(
-                CpiContext::new(
-                    ctx.accounts.system_program.to_account_info(),
-                    anchor_lang::system_program::Transfer {
-                        from: ctx.accounts.delegation.to_account_info(),
-                        to: ctx.accounts.user.to_account_info(),
-                    },
-                ),
-                remaining_amount,
-            )?;
+            ctx.accounts.delegation.sub_lamports(remaining_amount)?;
+            ctx.accounts.user.add_lamports(remaining_amount)?;
         }
 
         // Update delegation after transfer
@@ -272,164 +582,1913 @@ pub mod savefi {
 
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct InitializeMints<'info> {
-    #[account(init, payer = admin, space = 8 + 1, seeds = [b"mint_authority"], bump)]
-    pub mint_authority: Account<'info, MintAuthority>,
-    #[account(init, payer = admin, space = 8 + 32 + 8 + 1, seeds = [b"fee_account"], bump)]
-    pub fee_account: Account<'info, FeeAccount>,
-    #[account(init, payer = admin, space = 8 + 32 + 1 + 32, seeds = [b"config"], bump)]
-    pub config: Account<'info, ProtocolConfig>,
-    #[account(init, payer = admin, space = 8 + 1, seeds = [b"reentrancy_guard"], bump)]
-    pub reentrancy_guard: Account<'info, ReentrancyGuard>,
-    #[account(mut)]
-    pub save_token_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-}
+    /// Returns part of the delegated lamports while leaving expiry and operator untouched.
+    pub fn withdraw_from_delegation(ctx: Context<WithdrawFromDelegation>, amount: u64) -> Result<()> {
+        let config = &ctx.accounts.config;
+        require!(!config.is_paused(constants::PAUSE_REVOKE_DELEGATION), SaveFiError::RevokeDelegationPaused);
+        let delegation = &mut ctx.accounts.delegation;
+        require!(delegation.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        require!(amount > 0 && amount <= delegation.delegated_amount, SaveFiError::InvalidDelegationAmount);
+        let remaining = delegation.delegated_amount - amount;
+        require!(
+            remaining == 0 || remaining >= config.min_delegation,
+            SaveFiError::InvalidDelegationAmount
+        );
 
-#[derive(Accounts)]
-pub struct InitializeVault<'info> {
-    #[account(init, payer = user, space = 8 + 32 + 1 + 1 + 8 + 8 + 1 + 8, seeds = [b"vault", user.key().as_ref()], bump)]
-    pub vault: Account<'info, Vault>,
-    #[account(init, payer = user, space = 8 + 32 + 1, seeds = [b"proxy", user.key().as_ref()], bump)]
-    pub proxy_account: Account<'info, ProxyAccount>,
-    #[account(init, payer = user, associated_token::mint = save_token_mint, associated_token::authority = vault)]
-    pub vault_token_account: Account<'info, TokenAccount>,
-    /// CHECK: Validated in config
-    pub save_token_mint: AccountInfo<'info>,
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(has_one = admin @ SaveFiError::Unauthorized)]
-    pub config: Account<'info, ProtocolConfig>,
-    /// CHECK: This is the admin account
-    pub admin: AccountInfo<'info>,
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-}
+        let delegation_info = delegation.to_account_info();
+        let rent_exempt = Rent::get()?.minimum_balance(delegation_info.data_len());
+        require!(
+            delegation_info.lamports().saturating_sub(amount) >= rent_exempt,
+            SaveFiError::InsufficientDelegationBalance
+        );
+        delegation.delegated_amount = remaining;
 
-#[derive(Accounts)]
-pub struct DelegateFunds<'info> {
-    #[account(init, payer = user, space = 8 + 32 + 8 + 8 + 1, seeds = [b"delegation", user.key().as_ref()], bump)]
-    pub delegation: Account<'info, Delegation>,
-    #[account(mut)]
-    pub user: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+        // The delegation is program-owned, so lamports are moved directly
+        ctx.accounts.delegation.sub_lamports(amount)?;
+        ctx.accounts.user.add_lamports(amount)?;
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct AutoDeduct<'info> {
-    #[account(mut, seeds = [b"vault", proxy_account.owner.as_ref()], bump)]
-    pub vault: Account<'info, Vault>,
-    #[account(mut, seeds = [b"proxy", proxy_account.owner.as_ref()], bump)]
-    pub proxy_account: Account<'info, ProxyAccount>,
-    #[account(mut)]
-    pub vault_token_account: Account<'info, TokenAccount>,
-    /// CHECK: Validated in config
-    pub save_token_mint: AccountInfo<'info>,
-    #[account(seeds = [b"mint_authority"], bump = mint_authority.bump)]
-    pub mint_authority: Account<'info, MintAuthority>,
-    #[account(mut, seeds = [b"fee_account"], bump)]
-    pub fee_account: Account<'info, FeeAccount>,
-    #[account(mut, seeds = [b"delegation", vault.owner.as_ref()], bump)]
-    pub delegation: Account<'info, Delegation>,
-    /// CHECK: Validated as DEX pool or wallet
-    #[account(mut)]
-    pub destination: AccountInfo<'info>,
-    #[account(has_one = admin @ SaveFiError::Unauthorized)]
-    pub config: Account<'info, ProtocolConfig>,
-    /// CHECK: This is the admin account
-    pub admin: AccountInfo<'info>,
-    #[account(mut, seeds = [b"reentrancy_guard"], bump)]
-    pub reentrancy_guard: Account<'info, ReentrancyGuard>,
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-}
+    /// Rotates the key allowed to call `auto_deduct`; `None` disables deductions.
+    pub fn set_operator(ctx: Context<ConfigureDelegation>, operator: Option<Pubkey>) -> Result<()> {
+        let delegation = &mut ctx.accounts.delegation;
+        require!(delegation.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        delegation.operator = operator.unwrap_or_default();
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct UpdateVault<'info> {
-    #[account(mut, seeds = [b"vault", user.key().as_ref()], bump)]
-    pub vault: Account<'info, Vault>,
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(has_one = admin @ SaveFiError::Unauthorized)]
-    pub config: Account<'info, ProtocolConfig>,
-    /// CHECK: This is the admin account
-    pub admin: AccountInfo<'info>,
-}
+    /// Allows `auto_deduct` to send to `entry`, either the destination account itself
+    /// or the program that owns it.
+    pub fn add_allowed_destination(ctx: Context<ConfigureDelegation>, entry: Pubkey) -> Result<()> {
+        let delegation = &mut ctx.accounts.delegation;
+        require!(delegation.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        require!(!delegation.allowed_destinations.contains(&entry), SaveFiError::DestinationAlreadyAllowed);
+        require!(
+            delegation.allowed_destinations.len() < constants::MAX_ALLOWED_DESTINATIONS,
+            SaveFiError::AllowlistFull
+        );
+        delegation.allowed_destinations.push(entry);
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct Withdraw<'info> {
-    #[account(mut, seeds = [b"vault", user.key().as_ref()], bump)]
-    pub vault: Account<'info, Vault>,
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut)]
-    pub vault_token_account: Account<'info, TokenAccount>,
-    /// CHECK: Validated in config
-    pub save_token_mint: AccountInfo<'info>,
-    #[account(has_one = admin @ SaveFiError::Unauthorized)]
-    pub config: Account<'info, ProtocolConfig>,
-    /// CHECK: This is the admin account
-    pub admin: AccountInfo<'info>,
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-}
+    pub fn remove_allowed_destination(ctx: Context<ConfigureDelegation>, entry: Pubkey) -> Result<()> {
+        let delegation = &mut ctx.accounts.delegation;
+        require!(delegation.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        let index = delegation
+            .allowed_destinations
+            .iter()
+            .position(|allowed| *allowed == entry)
+            .ok_or(SaveFiError::DestinationNotFound)?;
+        delegation.allowed_destinations.swap_remove(index);
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct RenewSubscription<'info> {
-    #[account(mut, seeds = [b"vault", user.key().as_ref()], bump)]
-    pub vault: Account<'info, Vault>,
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut, seeds = [b"fee_account"], bump)]
-    pub fee_account: Account<'info, FeeAccount>,
-    pub system_program: Program<'info, System>,
-}
+    /// When enabled, `auto_deduct` only sends to accounts owned by a registered DEX program.
+    pub fn set_dex_only(ctx: Context<ConfigureDelegation>, dex_only: bool) -> Result<()> {
+        let delegation = &mut ctx.accounts.delegation;
+        require!(delegation.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        require!(!dex_only || delegation.is_native(), SaveFiError::DexOnlyUnsupported);
+        delegation.dex_only = dex_only;
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct RevokeDelegation<'info> {
-    #[account(mut, seeds = [b"delegation", user.key().as_ref()], bump)]
-    pub delegation: Account<'info, Delegation>,
-    #[account(mut)]
-    pub user: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+    /// Closes an empty, expired SOL delegation. Token delegations use `close_token_delegation`.
+    pub fn close_delegation(ctx: Context<CloseDelegation>) -> Result<()> {
+        require!(!ctx.accounts.config.is_paused(constants::PAUSE_REVOKE_DELEGATION), SaveFiError::RevokeDelegationPaused);
+        let delegation = &ctx.accounts.delegation;
+        require!(delegation.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        require!(delegation.is_native(), SaveFiError::NotNativeDelegation);
+        require!(delegation.delegated_amount == 0, SaveFiError::DelegationNotEmpty);
+        require!(
+            Clock::get()?.unix_timestamp >= delegation.delegation_expiry,
+            SaveFiError::DelegationStillActive
+        );
+        ctx.accounts.delegation_registry.remove(delegation.id);
+        Ok(())
+    }
 
-#[account]
-pub struct Vault {
-    pub owner: Pubkey,
-    pub savings_rate: u8,
-    pub lock_period_days: u8,
-    pub balance: u64,
-    pub lock_until: i64,
-    pub is_active: bool,
-    pub next_payment_due: i64,
-}
+    /// Closes an empty, expired token delegation together with its escrow, if
+    /// `revoke_token_delegation` has not closed the escrow already.
+    pub fn close_token_delegation(ctx: Context<CloseTokenDelegation>) -> Result<()> {
+        require!(!ctx.accounts.config.is_paused(constants::PAUSE_REVOKE_DELEGATION), SaveFiError::RevokeDelegationPaused);
+        let delegation = &ctx.accounts.delegation;
+        require!(delegation.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        require!(delegation.delegated_amount == 0, SaveFiError::DelegationNotEmpty);
+        require!(
+            Clock::get()?.unix_timestamp >= delegation.delegation_expiry,
+            SaveFiError::DelegationStillActive
+        );
+        ctx.accounts.delegation_registry.remove(delegation.id);
 
-#[account]
-pub struct Delegation {
-    pub owner: Pubkey,
-    pub delegated_amount: u64,
-    pub delegation_expiry: i64,
-    pub bump: u8,
-}
+        let escrow_info = ctx.accounts.escrow.to_account_info();
+        if escrow_info.data_is_empty() {
+            return Ok(());
+        }
+        let escrow = TokenAccount::try_deserialize(&mut &escrow_info.try_borrow_data()?[..])?;
+        require!(escrow.amount == 0, SaveFiError::DelegationNotEmpty);
+        let user_key = ctx.accounts.user.key();
+        close_escrow(
+            &ctx.accounts.token_program,
+            &ctx.accounts.mint.to_account_info(),
+            &escrow_info,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.delegation.to_account_info(),
+            &[b"delegation", user_key.as_ref(), &[delegation.id], &[delegation.bump]],
+        )
+    }
 
-#[account]
-pub struct MintAuthority {
-    pub bump: u8,
-}
+    pub fn close_vault(ctx: Context<CloseVault>) -> Result<()> {
+        require!(!ctx.accounts.config.is_paused(constants::PAUSE_WITHDRAW), SaveFiError::WithdrawPaused);
+        let vault = &ctx.accounts.vault;
+        require!(vault.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        require!(
+            vault.balance == 0 && vault.open_token_mints == 0 && ctx.accounts.vault_token_account.amount == 0,
+            SaveFiError::VaultNotEmpty
+        );
+        require!(
+            Clock::get()?.unix_timestamp >= vault.lock_until,
+            SaveFiError::VaultLocked
+        );
+
+        // The SaveSOL account is owned by the vault PDA, so it signs the close
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.vault_token_account.to_account_info(),
+                destination: ctx.accounts.user.to_account_info(),
+                authority: ctx.accounts.vault.to_account_info(),
+            },
+            &[&[b"vault", ctx.accounts.user.key().as_ref(), &[ctx.bumps.vault]]],
+        ))?;
+        Ok(())
+    }
+
+    /// Sets a personal daily limit below the protocol one; zero falls back to the protocol limit.
+    /// Token delegations are capped by their mint's daily limit at deduction time.
+    pub fn set_daily_limit(ctx: Context<ConfigureDelegation>, daily_limit: u64) -> Result<()> {
+        let delegation = &mut ctx.accounts.delegation;
+        require!(
+            !delegation.is_native() || daily_limit <= constants::DAILY_LIMIT_SOL,
+            SaveFiError::InvalidDailyLimit
+        );
+        require!(delegation.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        delegation.daily_limit = daily_limit;
+        Ok(())
+    }
+
+    /// Sets a minimum interval between deductions longer than the protocol default;
+    /// zero falls back to the protocol default.
+    pub fn set_deduction_cooldown(ctx: Context<ConfigureDelegation>, cooldown_secs: i64) -> Result<()> {
+        require!(
+            cooldown_secs == 0
+                || (Delegation::default_cooldown()..=constants::MAX_DELEGATION_COOLDOWN_SECS).contains(&cooldown_secs),
+            SaveFiError::InvalidCooldown
+        );
+        let delegation = &mut ctx.accounts.delegation;
+        require!(delegation.owner == ctx.accounts.user.key(), SaveFiError::Unauthorized);
+        delegation.cooldown_secs = cooldown_secs;
+        Ok(())
+    }
+
+    pub fn collect_fees(ctx: Context<CollectFees>, amount: Option<u64>) -> Result<()> {
+        ctx.accounts.role_grant.require_role(Role::FeeCollector, &ctx.accounts.config)?;
+        let now = Clock::get()?.unix_timestamp;
+        let fee_account = &mut ctx.accounts.fee_account;
+        require!(
+            now.saturating_sub(fee_account.last_collection_time) >= fee_account.collection_cooldown,
+            SaveFiError::CollectionCooldown
+        );
+
+        // Reconcile the tracked balance with what the PDA actually holds above rent,
+        // since subscription payments land here without going through `balance`.
+        let fee_info = fee_account.to_account_info();
+        let rent_exempt = Rent::get()?.minimum_balance(fee_info.data_len());
+        let available = fee_info.lamports().saturating_sub(rent_exempt);
+        fee_account.balance = available;
+
+        let amount = amount.unwrap_or(available);
+        require!(amount > 0, SaveFiError::NoFeesToCollect);
+        require!(amount <= available, SaveFiError::InsufficientFeeBalance);
+
+        fee_account.balance = available - amount;
+        fee_account.last_collection_time = now;
+
+        // The fee account is program-owned, so lamports are moved directly
+        ctx.accounts.fee_account.sub_lamports(amount)?;
+        ctx.accounts.treasury.add_lamports(amount)?;
+        Ok(())
+    }
+
+    /// Sweeps token fees for one mint; the collection cooldown only applies to lamport fees.
+    pub fn collect_token_fees(ctx: Context<CollectTokenFees>, amount: Option<u64>) -> Result<()> {
+        ctx.accounts.role_grant.require_role(Role::FeeCollector, &ctx.accounts.config)?;
+        let available = ctx.accounts.fee_token_account.amount;
+        let amount = amount.unwrap_or(available);
+        require!(amount > 0, SaveFiError::NoFeesToCollect);
+        require!(amount <= available, SaveFiError::InsufficientFeeBalance);
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.fee_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.treasury_token_account.to_account_info(),
+                    authority: ctx.accounts.fee_account.to_account_info(),
+                },
+                &[&[b"fee_account".as_ref(), &[ctx.bumps.fee_account]]],
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+        Ok(())
+    }
+
+    #[access_control(admin_only(&ctx))]
+    pub fn set_collection_cooldown(ctx: Context<SetCollectionCooldown>, cooldown_secs: i64) -> Result<()> {
+        require!(
+            (0..=constants::MAX_FEE_COLLECTION_COOLDOWN_SECS).contains(&cooldown_secs),
+            SaveFiError::InvalidCooldown
+        );
+        ctx.accounts.fee_account.collection_cooldown = cooldown_secs;
+        Ok(())
+    }
+
+    pub fn pause_protocol(ctx: Context<PauseProtocol>, reason: u8) -> Result<()> {
+        ctx.accounts.role_grant.require_role(Role::Pauser, &ctx.accounts.config)?;
+        let config = &mut ctx.accounts.config;
+        require!(config.pause_flags != constants::PAUSE_ALL, SaveFiError::AlreadyPaused);
+        config.set_pause_flags(constants::PAUSE_ALL, reason, ctx.accounts.pauser.key())
+    }
+
+    #[access_control(admin_only(&ctx))]
+    pub fn unpause_protocol(ctx: Context<UnpauseProtocol>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(config.pause_flags != 0, SaveFiError::NotPaused);
+        config.set_pause_flags(0, 0, ctx.accounts.admin.key())
+    }
+
+    pub fn set_pause_flags(ctx: Context<PauseProtocol>, flags: u16, reason: u8) -> Result<()> {
+        ctx.accounts.role_grant.require_role(Role::Pauser, &ctx.accounts.config)?;
+        require!(flags != 0 && flags & !constants::PAUSE_ALL == 0, SaveFiError::InvalidPauseFlags);
+        let config = &mut ctx.accounts.config;
+        let new_flags = config.pause_flags | flags;
+        config.set_pause_flags(new_flags, reason, ctx.accounts.pauser.key())
+    }
+
+    #[access_control(admin_only(&ctx))]
+    pub fn clear_pause_flags(ctx: Context<UnpauseProtocol>, flags: u16) -> Result<()> {
+        require!(flags != 0 && flags & !constants::PAUSE_ALL == 0, SaveFiError::InvalidPauseFlags);
+        let config = &mut ctx.accounts.config;
+        let new_flags = config.pause_flags & !flags;
+        let reason = if new_flags == 0 { 0 } else { config.pause_reason };
+        config.set_pause_flags(new_flags, reason, ctx.accounts.admin.key())
+    }
+
+    #[access_control(admin_only(&ctx))]
+    pub fn propose_admin(ctx: Context<ManageAdmin>, new_admin: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(
+            new_admin != Pubkey::default() && new_admin != config.admin,
+            SaveFiError::InvalidAdmin
+        );
+        config.pending_admin = new_admin;
+        emit!(AdminProposed {
+            admin: config.admin,
+            pending_admin: new_admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    #[access_control(admin_only(&ctx))]
+    pub fn cancel_admin_proposal(ctx: Context<ManageAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(config.pending_admin != Pubkey::default(), SaveFiError::NoPendingAdmin);
+        let cancelled = config.pending_admin;
+        config.pending_admin = Pubkey::default();
+        emit!(AdminProposalCancelled {
+            admin: config.admin,
+            cancelled_admin: cancelled,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    pub fn initialize_governance(ctx: Context<InitializeGovernance>, signers: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        require!(!ctx.accounts.config.multisig_enabled, SaveFiError::MultisigAlreadyEnabled);
+        let governance = &mut ctx.accounts.governance;
+        governance.set_signers(signers, threshold)?;
+        governance.bump = ctx.bumps.governance;
+        ctx.accounts.config.multisig_enabled = true;
+
+        emit!(GovernanceSignersUpdated {
+            signers: governance.signers.clone(),
+            threshold,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    pub fn update_governance_signers(ctx: Context<UpdateGovernance>, signers: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        let governance = &mut ctx.accounts.governance;
+        // Rotation must be approved by the current signer set
+        governance.verify_approvals(&ctx.accounts.signer, ctx.remaining_accounts)?;
+        governance.set_signers(signers, threshold)?;
+
+        emit!(GovernanceSignersUpdated {
+            signers: governance.signers.clone(),
+            threshold,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    #[access_control(admin_only(&ctx))]
+    pub fn queue_param_change(ctx: Context<QueueParamChange>, update: ParamUpdate, eta: i64) -> Result<()> {
+        update.validate(&ctx.accounts.config, &ctx.accounts.fee_account)?;
+        let now = Clock::get()?.unix_timestamp;
+        let config = &mut ctx.accounts.config;
+        require!(eta >= now + config.timelock_delay, SaveFiError::EtaTooEarly);
+
+        let change = &mut ctx.accounts.param_change;
+        change.id = config.next_change_id;
+        change.update = update.clone();
+        change.eta = eta;
+        change.proposer = ctx.accounts.admin.key();
+        change.bump = ctx.bumps.param_change;
+        config.next_change_id += 1;
+
+        emit!(ParamChangeQueued {
+            id: change.id,
+            update,
+            eta,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn execute_param_change(ctx: Context<ExecuteParamChange>) -> Result<()> {
+        ctx.accounts.role_grant.require_role(Role::Keeper, &ctx.accounts.config)?;
+        let now = Clock::get()?.unix_timestamp;
+        let change = &ctx.accounts.param_change;
+        change.check_executable(now)?;
+        change.update.validate(&ctx.accounts.config, &ctx.accounts.fee_account)?;
+
+        let config = &mut ctx.accounts.config;
+        match change.update {
+            ParamUpdate::FeeRate(rate) => ctx.accounts.fee_account.set_fee_rate(rate, now, config.timelock_delay)?,
+            ParamUpdate::SubscriptionFee(fee) => config.subscription_fee = fee,
+            ParamUpdate::DelegationLimits { min, max } => {
+                config.min_delegation = min;
+                config.max_delegation = max;
+            }
+            ParamUpdate::TimelockDelay(delay) => config.timelock_delay = delay,
+            ParamUpdate::SubscriptionPeriod(days) => config.limits.subscription_period_days = days,
+            ParamUpdate::MaxFeeRate(rate) => {
+                require!(ctx.accounts.fee_account.fee_rate <= rate, SaveFiError::InvalidFeeRate);
+                config.limits.max_fee_rate = rate;
+            }
+            ParamUpdate::MintLimits { mint, min, max, daily_limit } => {
+                let mint_config = ctx.accounts.mint_config.as_mut().ok_or(SaveFiError::InvalidMint)?;
+                require_keys_eq!(mint_config.mint, mint, SaveFiError::InvalidMint);
+                mint_config.min_delegation = min;
+                mint_config.max_delegation = max;
+                mint_config.daily_limit = daily_limit;
+            }
+        }
+
+        emit!(ParamChangeExecuted {
+            id: change.id,
+            update: change.update.clone(),
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    #[access_control(admin_only(&ctx))]
+    pub fn cancel_param_change(ctx: Context<CancelParamChange>) -> Result<()> {
+        emit!(ParamChangeCancelled {
+            id: ctx.accounts.param_change.id,
+            update: ctx.accounts.param_change.update.clone(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Lowers the fee rate immediately. Increases go through `queue_param_change`.
+    #[access_control(admin_only(&ctx))]
+    pub fn update_fee(ctx: Context<UpdateFee>, new_fee_rate: u8) -> Result<()> {
+        let fee_account = &mut ctx.accounts.fee_account;
+        require!(new_fee_rate < fee_account.fee_rate, SaveFiError::FeeIncreaseRequiresTimelock);
+        fee_account.set_fee_rate(new_fee_rate, Clock::get()?.unix_timestamp, ctx.accounts.config.timelock_delay)
+    }
+
+    /// Updates the limits that apply immediately. Subscription fee, delegation limits,
+    /// the subscription period and fee cap increases go through `queue_param_change`.
+    #[access_control(admin_only(&ctx))]
+    pub fn update_limits(ctx: Context<UpdateLimits>, limits: ProtocolLimits) -> Result<()> {
+        limits.validate()?;
+        let current = &ctx.accounts.config.limits;
+        require!(
+            limits.subscription_period_days == current.subscription_period_days
+                && limits.max_fee_rate <= current.max_fee_rate,
+            SaveFiError::LimitChangeRequiresTimelock
+        );
+        require!(ctx.accounts.fee_account.fee_rate <= limits.max_fee_rate, SaveFiError::InvalidFeeRate);
+        ctx.accounts.config.limits = limits.clone();
+
+        emit!(ProtocolLimitsUpdated {
+            limits,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    #[access_control(admin_only(&ctx))]
+    pub fn grant_role(ctx: Context<GrantRole>, role: Role, member: Pubkey) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let role_grant = &mut ctx.accounts.role_grant;
+        role_grant.role = role;
+        role_grant.member = member;
+        role_grant.granted_by = ctx.accounts.admin.key();
+        role_grant.granted_at = now;
+        role_grant.epoch = ctx.accounts.config.admin_epoch;
+        role_grant.bump = ctx.bumps.role_grant;
+
+        emit!(RoleGranted {
+            role,
+            member,
+            granted_by: role_grant.granted_by,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    #[access_control(admin_only(&ctx))]
+    pub fn revoke_role(ctx: Context<RevokeRole>) -> Result<()> {
+        emit!(RoleRevoked {
+            role: ctx.accounts.role_grant.role,
+            member: ctx.accounts.role_grant.member,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    #[access_control(admin_only(&ctx))]
+    pub fn add_dex_program(ctx: Context<AddDexProgram>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let dex_program = &mut ctx.accounts.dex_program;
+        dex_program.program_id = ctx.accounts.program.key();
+        dex_program.enabled = true;
+        dex_program.updated_at = now;
+        dex_program.bump = ctx.bumps.dex_program;
+
+        emit!(DexProgramUpdated {
+            program_id: dex_program.program_id,
+            enabled: true,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    #[access_control(admin_only(&ctx))]
+    pub fn set_dex_program_enabled(ctx: Context<UpdateDexProgram>, enabled: bool) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let dex_program = &mut ctx.accounts.dex_program;
+        dex_program.enabled = enabled;
+        dex_program.updated_at = now;
+
+        emit!(DexProgramUpdated {
+            program_id: dex_program.program_id,
+            enabled,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    #[access_control(admin_only(&ctx))]
+    pub fn remove_dex_program(ctx: Context<RemoveDexProgram>) -> Result<()> {
+        emit!(DexProgramRemoved {
+            program_id: ctx.accounts.dex_program.program_id,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Enables an SPL mint for token delegations and sets its per-mint limits, in base units.
+    /// Once a mint is configured its limits only tighten here; raising them goes through
+    /// `queue_param_change` like the SOL delegation limits.
+    #[access_control(admin_only(&ctx))]
+    pub fn set_mint_config(
+        ctx: Context<SetMintConfig>,
+        min_delegation: u64,
+        max_delegation: u64,
+        daily_limit: u64,
+        enabled: bool,
+    ) -> Result<()> {
+        MintConfig::check_limits(min_delegation, max_delegation, daily_limit)?;
+        check_mint_extensions(&ctx.accounts.mint.to_account_info())?;
+        let now = Clock::get()?.unix_timestamp;
+        let mint_config = &mut ctx.accounts.mint_config;
+        mint_config.check_immediate_update(min_delegation, max_delegation, daily_limit)?;
+        mint_config.mint = ctx.accounts.mint.key();
+        mint_config.min_delegation = min_delegation;
+        mint_config.max_delegation = max_delegation;
+        mint_config.daily_limit = daily_limit;
+        mint_config.enabled = enabled;
+        mint_config.bump = ctx.bumps.mint_config;
+
+        emit!(MintConfigUpdated {
+            mint: mint_config.mint,
+            min_delegation,
+            max_delegation,
+            daily_limit,
+            enabled,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let new_admin = ctx.accounts.new_admin.key();
+        let config = &mut ctx.accounts.config;
+        let previous_admin = config.admin;
+
+        // Grants issued by the previous admin stop working until re-granted
+        config.admin = new_admin;
+        config.pending_admin = Pubkey::default();
+        config.admin_epoch += 1;
+
+        emit!(AdminTransferred {
+            previous_admin,
+            new_admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+}
+
+/// The caller's timestamp is only a staleness hint; every time check uses the clock.
+/// Privileged instruction contexts carry the config, the approving `admin` signer and,
+/// in multisig mode, the governance account.
+pub trait AdminAccounts<'info> {
+    fn config(&self) -> &ProtocolConfig;
+    fn admin(&self) -> &Signer<'info>;
+    fn governance(&self) -> Option<&Governance>;
+}
+
+macro_rules! admin_accounts {
+    ($($context:ident),* $(,)?) => {$(
+        impl<'info> AdminAccounts<'info> for $context<'info> {
+            fn config(&self) -> &ProtocolConfig {
+                &self.config
+            }
+            fn admin(&self) -> &Signer<'info> {
+                &self.admin
+            }
+            fn governance(&self) -> Option<&Governance> {
+                self.governance.as_deref()
+            }
+        }
+    )*};
+}
+
+admin_accounts!(
+    SetCollectionCooldown,
+    UnpauseProtocol,
+    ManageAdmin,
+    QueueParamChange,
+    CancelParamChange,
+    UpdateFee,
+    UpdateLimits,
+    GrantRole,
+    RevokeRole,
+    AddDexProgram,
+    UpdateDexProgram,
+    RemoveDexProgram,
+    SetMintConfig,
+);
+
+/// Access control for privileged instructions; see `ProtocolConfig::authorize_admin`
+/// for who may approve and which remaining accounts carry the approvals.
+fn admin_only<'info, T: AdminAccounts<'info> + anchor_lang::Bumps>(ctx: &Context<'_, '_, '_, '_, T>) -> Result<()> {
+    ctx.accounts.config().authorize_admin(ctx.accounts.admin(), ctx.accounts.governance(), ctx.remaining_accounts)
+}
+
+fn check_timestamp_hint(timestamp: Option<i64>, now: i64) -> Result<()> {
+    if let Some(signed_at) = timestamp {
+        require!(signed_at <= now.saturating_add(constants::MAX_CLOCK_SKEW_SECS), SaveFiError::InvalidTimestamp);
+        require!(
+            now.saturating_sub(signed_at) <= constants::MAX_TIMESTAMP_STALENESS_SECS,
+            SaveFiError::StaleTimestamp
+        );
+    }
+    Ok(())
+}
+
+/// Closes a delegation escrow, first sweeping any withheld transfer fees to the
+/// mint since Token-2022 refuses to close an account that still holds them.
+fn close_escrow<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    mint: &AccountInfo<'info>,
+    escrow: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    delegation: &AccountInfo<'info>,
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    if transfer_fee_config(mint)?.is_some() {
+        let harvest = spl_token_2022::extension::transfer_fee::instruction::harvest_withheld_tokens_to_mint(
+            token_program.key,
+            mint.key,
+            &[escrow.key],
+        )?;
+        anchor_lang::solana_program::program::invoke(
+            &harvest,
+            &[mint.clone(), escrow.clone(), token_program.to_account_info()],
+        )?;
+    }
+    token_interface::close_account(CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        CloseAccount {
+            account: escrow.clone(),
+            destination: destination.clone(),
+            authority: delegation.clone(),
+        },
+        &[signer_seeds],
+    ))
+}
+
+/// `rate` percent of `amount`, rounded down.
+fn percent_of(amount: u64, rate: u8) -> Result<u64> {
+    let value = (amount as u128)
+        .checked_mul(rate as u128)
+        .ok_or(SaveFiError::InvalidSaveAmount)?
+        / 100;
+    Ok(value as u64)
+}
+
+/// Transfer fee settings of a Token-2022 mint; `None` for legacy mints and mints without the extension.
+fn transfer_fee_config(mint: &AccountInfo) -> Result<Option<TransferFeeConfig>> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(None);
+    }
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(state.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+/// Fee the mint withholds from a transfer of `amount` during `epoch`.
+fn transfer_fee(mint: &AccountInfo, amount: u64, epoch: u64) -> Result<u64> {
+    match transfer_fee_config(mint)? {
+        Some(config) => config
+            .calculate_epoch_fee(epoch, amount)
+            .ok_or_else(|| error!(SaveFiError::InvalidSaveAmount)),
+        None => Ok(0),
+    }
+}
+
+/// Amount to send so that exactly `amount` arrives after the mint's transfer fee.
+fn with_transfer_fee(mint: &AccountInfo, amount: u64, epoch: u64) -> Result<u64> {
+    match transfer_fee_config(mint)? {
+        Some(config) if amount > 0 => config
+            .calculate_inverse_epoch_fee(epoch, amount)
+            .and_then(|fee| amount.checked_add(fee))
+            .ok_or_else(|| error!(SaveFiError::InvalidSaveAmount)),
+        _ => Ok(amount),
+    }
+}
+
+/// Rejects Token-2022 extensions that could strand or pull tokens out of an escrow.
+/// Transfer hooks are included because escrow transfers don't forward hook accounts, and
+/// a frozen default account state because new escrow and vault accounts couldn't receive.
+fn check_mint_extensions(mint: &AccountInfo) -> Result<()> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let unsupported = [
+        ExtensionType::NonTransferable,
+        ExtensionType::PermanentDelegate,
+        ExtensionType::TransferHook,
+        ExtensionType::DefaultAccountState,
+    ];
+    require!(
+        !state.get_extension_types()?.iter().any(|extension| unsupported.contains(extension)),
+        SaveFiError::UnsupportedMintExtension
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeMints<'info> {
+    #[account(init, payer = admin, space = 8 + 1, seeds = [b"mint_authority"], bump)]
+    pub mint_authority: Account<'info, MintAuthority>,
+    #[account(init, payer = admin, space = 8 + 8 + 1 + 8 + 8 + 1 + 8, seeds = [b"fee_account"], bump)]
+    pub fee_account: Account<'info, FeeAccount>,
+    #[account(init, payer = admin, space = 8 + 32 + 2 + 32 + 1 + 8 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + 6 + 8, seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(init, payer = admin, space = 8 + 1, seeds = [b"reentrancy_guard"], bump)]
+    pub reentrancy_guard: Account<'info, ReentrancyGuard>,
+    #[account(init, payer = admin, space = 8 + 8 + 1, seeds = [b"reserve"], bump)]
+    pub reserve: Account<'info, SavingsReserve>,
+    #[account(mut)]
+    pub save_token_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeVault<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + 32 + 1 + 1 + 8 + 8 + 1 + 8 + 4 + 16 * constants::MAX_VAULT_TRANCHES + 2,
+        seeds = [b"vault", user.key().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(init, payer = user, space = 8 + 32 + 1, seeds = [b"proxy", user.key().as_ref()], bump)]
+    pub proxy_account: Account<'info, ProxyAccount>,
+    #[account(init, payer = user, associated_token::mint = save_token_mint, associated_token::authority = vault, associated_token::token_program = token_program)]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Validated in config
+    pub save_token_mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u8)]
+pub struct DelegateFunds<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + 32 + 1 + 8 + 8 + 1 + 8 + 8 + 8 + 8 + 8 + 32 + 4 + 32 * constants::MAX_ALLOWED_DESTINATIONS + 1 + 32,
+        seeds = [b"delegation", user.key().as_ref(), [id].as_ref()],
+        bump
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 32 + 4 + constants::MAX_DELEGATIONS_PER_USER + 1,
+        seeds = [b"delegations", user.key().as_ref()],
+        bump
+    )]
+    pub delegation_registry: Account<'info, DelegationRegistry>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TopUpDelegation<'info> {
+    #[account(
+        mut,
+        seeds = [b"delegation", user.key().as_ref(), [delegation.id].as_ref()],
+        bump = delegation.bump,
+        constraint = delegation.is_native() @ SaveFiError::NotNativeDelegation
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendDelegation<'info> {
+    #[account(mut, seeds = [b"delegation", user.key().as_ref(), [delegation.id].as_ref()], bump = delegation.bump)]
+    pub delegation: Account<'info, Delegation>,
+    pub user: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(trade_id: [u8; 16])]
+pub struct AutoDeduct<'info> {
+    #[account(mut, seeds = [b"vault", proxy_account.owner.as_ref()], bump)]
+    pub vault: Account<'info, Vault>,
+    #[account(mut, seeds = [b"proxy", proxy_account.owner.as_ref()], bump)]
+    pub proxy_account: Account<'info, ProxyAccount>,
+    #[account(
+        mut,
+        associated_token::mint = save_token_mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Must be the SaveSOL mint recorded in config
+    #[account(mut, address = config.save_token_mint @ SaveFiError::InvalidMint)]
+    pub save_token_mint: AccountInfo<'info>,
+    #[account(seeds = [b"mint_authority"], bump = mint_authority.bump)]
+    pub mint_authority: Account<'info, MintAuthority>,
+    #[account(mut, seeds = [b"fee_account"], bump)]
+    pub fee_account: Account<'info, FeeAccount>,
+    #[account(mut, seeds = [b"reserve"], bump = reserve.bump)]
+    pub reserve: Account<'info, SavingsReserve>,
+    #[account(
+        mut,
+        seeds = [b"delegation", vault.owner.as_ref(), [delegation.id].as_ref()],
+        bump = delegation.bump,
+        constraint = delegation.is_native() @ SaveFiError::NotNativeDelegation,
+        constraint = delegation.operator != Pubkey::default() @ SaveFiError::OperatorNotSet,
+        constraint = delegation.operator == operator.key() @ SaveFiError::UnauthorizedOperator
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(mut)]
+    pub operator: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = operator,
+        space = 8 + 32 + 16 + 8 + 8 + 32 + 1,
+        seeds = [b"receipt", delegation.key().as_ref(), trade_id.as_ref()],
+        bump
+    )]
+    pub trade_receipt: Account<'info, TradeReceipt>,
+    /// CHECK: Checked against the delegation allowlist and, in DEX-only mode, the DEX registry
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+    #[account(seeds = [b"dex", destination.owner.as_ref()], bump = dex_program.bump)]
+    pub dex_program: Option<Account<'info, DexProgram>>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(mut, seeds = [b"reentrancy_guard"], bump)]
+    pub reentrancy_guard: Account<'info, ReentrancyGuard>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CloseTradeReceipt<'info> {
+    #[account(
+        mut,
+        close = payer,
+        has_one = payer,
+        seeds = [b"receipt", trade_receipt.delegation.as_ref(), trade_receipt.trade_id.as_ref()],
+        bump = trade_receipt.bump
+    )]
+    pub trade_receipt: Account<'info, TradeReceipt>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u8)]
+pub struct DelegateTokens<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + 32 + 1 + 8 + 8 + 1 + 8 + 8 + 8 + 8 + 8 + 32 + 4 + 32 * constants::MAX_ALLOWED_DESTINATIONS + 1 + 32,
+        seeds = [b"delegation", user.key().as_ref(), [id].as_ref()],
+        bump
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 32 + 4 + constants::MAX_DELEGATIONS_PER_USER + 1,
+        seeds = [b"delegations", user.key().as_ref()],
+        bump
+    )]
+    pub delegation_registry: Account<'info, DelegationRegistry>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(seeds = [b"mint_config", mint.key().as_ref()], bump = mint_config.bump)]
+    pub mint_config: Account<'info, MintConfig>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = mint,
+        associated_token::authority = delegation,
+        associated_token::token_program = token_program
+    )]
+    pub escrow: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = mint, token::authority = user, token::token_program = token_program)]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds = [b"vault", user.key().as_ref()], bump)]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 32 + 32 + 8 + 4 + 16 * constants::MAX_VAULT_TRANCHES + 1,
+        seeds = [b"token_savings", vault.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub token_savings: Account<'info, TokenSavings>,
+    /// Receives the saved share of each deduction
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(trade_id: [u8; 16])]
+pub struct AutoDeductTokens<'info> {
+    #[account(mut, seeds = [b"vault", delegation.owner.as_ref()], bump)]
+    pub vault: Account<'info, Vault>,
+    #[account(mut, seeds = [b"token_savings", vault.key().as_ref(), mint.key().as_ref()], bump = token_savings.bump)]
+    pub token_savings: Account<'info, TokenSavings>,
+    #[account(mut, associated_token::mint = mint, associated_token::authority = vault, associated_token::token_program = token_program)]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds = [b"fee_account"], bump)]
+    pub fee_account: Account<'info, FeeAccount>,
+    #[account(mut, associated_token::mint = mint, associated_token::authority = fee_account, associated_token::token_program = token_program)]
+    pub fee_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"delegation", delegation.owner.as_ref(), [delegation.id].as_ref()],
+        bump = delegation.bump,
+        constraint = delegation.mint == mint.key() @ SaveFiError::InvalidMint,
+        constraint = delegation.operator != Pubkey::default() @ SaveFiError::OperatorNotSet,
+        constraint = delegation.operator == operator.key() @ SaveFiError::UnauthorizedOperator
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(mut, associated_token::mint = mint, associated_token::authority = delegation, associated_token::token_program = token_program)]
+    pub escrow: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(seeds = [b"mint_config", mint.key().as_ref()], bump = mint_config.bump)]
+    pub mint_config: Account<'info, MintConfig>,
+    #[account(mut)]
+    pub operator: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = operator,
+        space = 8 + 32 + 16 + 8 + 8 + 32 + 1,
+        seeds = [b"receipt", delegation.key().as_ref(), trade_id.as_ref()],
+        bump
+    )]
+    pub trade_receipt: Account<'info, TradeReceipt>,
+    /// Checked against the delegation allowlist by address or token account owner
+    #[account(mut, token::mint = mint, token::token_program = token_program)]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> AutoDeductTokens<'info> {
+    /// Moves `amount` out of the escrow, signed by the delegation PDA.
+    fn pay_out(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        let delegation = &self.delegation;
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: self.escrow.to_account_info(),
+                    mint: self.mint.to_account_info(),
+                    to,
+                    authority: delegation.to_account_info(),
+                },
+                &[&[b"delegation", delegation.owner.as_ref(), &[delegation.id], &[delegation.bump]]],
+            ),
+            amount,
+            self.mint.decimals,
+        )
+    }
+}
+
+#[derive(Accounts)]
+pub struct RevokeTokenDelegation<'info> {
+    #[account(
+        mut,
+        seeds = [b"delegation", user.key().as_ref(), [delegation.id].as_ref()],
+        bump = delegation.bump,
+        constraint = delegation.mint == mint.key() @ SaveFiError::InvalidMint
+    )]
+    pub delegation: Account<'info, Delegation>,
+    /// Writable so withheld transfer fees can be harvested before the escrow closes
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, associated_token::mint = mint, associated_token::authority = delegation, associated_token::token_program = token_program)]
+    pub escrow: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = mint, token::authority = user, token::token_program = token_program)]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawSavedTokens<'info> {
+    #[account(mut, seeds = [b"vault", user.key().as_ref()], bump)]
+    pub vault: Account<'info, Vault>,
+    #[account(mut, seeds = [b"token_savings", vault.key().as_ref(), mint.key().as_ref()], bump = token_savings.bump)]
+    pub token_savings: Account<'info, TokenSavings>,
+    /// SaveSOL is redeemed through `withdraw` instead
+    #[account(constraint = mint.key() != config.save_token_mint @ SaveFiError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, associated_token::mint = mint, associated_token::authority = vault, associated_token::token_program = token_program)]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = mint, token::authority = user, token::token_program = token_program)]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub user: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateVault<'info> {
+    #[account(mut, seeds = [b"vault", user.key().as_ref()], bump)]
+    pub vault: Account<'info, Vault>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(mut, seeds = [b"vault", user.key().as_ref()], bump)]
+    pub vault: Account<'info, Vault>,
+    #[account(mut, seeds = [b"reserve"], bump = reserve.bump)]
+    pub reserve: Account<'info, SavingsReserve>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        associated_token::mint = save_token_mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Must be the SaveSOL mint recorded in config
+    #[account(mut, address = config.save_token_mint @ SaveFiError::InvalidMint)]
+    pub save_token_mint: AccountInfo<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetVaultBalances<'info> {
+    #[account(seeds = [b"vault", vault.owner.as_ref()], bump)]
+    pub vault: Account<'info, Vault>,
+}
+
+#[derive(Accounts)]
+pub struct RenewSubscription<'info> {
+    #[account(mut, seeds = [b"vault", user.key().as_ref()], bump)]
+    pub vault: Account<'info, Vault>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [b"fee_account"], bump)]
+    pub fee_account: Account<'info, FeeAccount>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeDelegation<'info> {
+    #[account(
+        mut,
+        seeds = [b"delegation", user.key().as_ref(), [delegation.id].as_ref()],
+        bump = delegation.bump,
+        constraint = delegation.is_native() @ SaveFiError::NotNativeDelegation
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFromDelegation<'info> {
+    #[account(
+        mut,
+        seeds = [b"delegation", user.key().as_ref(), [delegation.id].as_ref()],
+        bump = delegation.bump,
+        constraint = delegation.is_native() @ SaveFiError::NotNativeDelegation
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CloseDelegation<'info> {
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"delegation", user.key().as_ref(), [delegation.id].as_ref()],
+        bump = delegation.bump
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(mut, seeds = [b"delegations", user.key().as_ref()], bump = delegation_registry.bump)]
+    pub delegation_registry: Account<'info, DelegationRegistry>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CloseTokenDelegation<'info> {
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"delegation", user.key().as_ref(), [delegation.id].as_ref()],
+        bump = delegation.bump,
+        constraint = delegation.mint == mint.key() @ SaveFiError::InvalidMint
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(mut, seeds = [b"delegations", user.key().as_ref()], bump = delegation_registry.bump)]
+    pub delegation_registry: Account<'info, DelegationRegistry>,
+    /// Writable so withheld transfer fees can be harvested before the escrow closes
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: The delegation's escrow address; it may already be closed by `revoke_token_delegation`
+    #[account(
+        mut,
+        address = anchor_spl::associated_token::get_associated_token_address_with_program_id(
+            &delegation.key(),
+            &mint.key(),
+            &token_program.key()
+        )
+    )]
+    pub escrow: AccountInfo<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CloseVault<'info> {
+    #[account(mut, close = user, seeds = [b"vault", user.key().as_ref()], bump)]
+    pub vault: Account<'info, Vault>,
+    #[account(mut, close = user, seeds = [b"proxy", user.key().as_ref()], bump = proxy_account.bump)]
+    pub proxy_account: Account<'info, ProxyAccount>,
+    #[account(mut, associated_token::mint = save_token_mint, associated_token::authority = vault, associated_token::token_program = token_program)]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Must be the SaveSOL mint recorded in config
+    #[account(address = config.save_token_mint @ SaveFiError::InvalidMint)]
+    pub save_token_mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureDelegation<'info> {
+    #[account(mut, seeds = [b"delegation", user.key().as_ref(), [delegation.id].as_ref()], bump = delegation.bump)]
+    pub delegation: Account<'info, Delegation>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    #[account(mut, seeds = [b"fee_account"], bump)]
+    pub fee_account: Account<'info, FeeAccount>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub collector: Signer<'info>,
+    #[account(seeds = [b"role", role_grant.role.seed().as_ref(), collector.key().as_ref()], bump = role_grant.bump)]
+    pub role_grant: Account<'info, RoleGrant>,
+    /// CHECK: Any account chosen by the fee collector to receive the fees
+    #[account(mut)]
+    pub treasury: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CollectTokenFees<'info> {
+    #[account(seeds = [b"fee_account"], bump)]
+    pub fee_account: Account<'info, FeeAccount>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, associated_token::mint = mint, associated_token::authority = fee_account, associated_token::token_program = token_program)]
+    pub fee_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = mint, token::token_program = token_program)]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub collector: Signer<'info>,
+    #[account(seeds = [b"role", role_grant.role.seed().as_ref(), collector.key().as_ref()], bump = role_grant.bump)]
+    pub role_grant: Account<'info, RoleGrant>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetCollectionCooldown<'info> {
+    #[account(mut, seeds = [b"fee_account"], bump)]
+    pub fee_account: Account<'info, FeeAccount>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub admin: Signer<'info>,
+    #[account(seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Option<Account<'info, Governance>>,
+}
+
+#[derive(Accounts)]
+pub struct PauseProtocol<'info> {
+    #[account(mut, seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub pauser: Signer<'info>,
+    #[account(seeds = [b"role", role_grant.role.seed().as_ref(), pauser.key().as_ref()], bump = role_grant.bump)]
+    pub role_grant: Account<'info, RoleGrant>,
+}
+
+#[derive(Accounts)]
+pub struct UnpauseProtocol<'info> {
+    #[account(mut, seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub admin: Signer<'info>,
+    #[account(seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Option<Account<'info, Governance>>,
+}
+
+#[derive(Accounts)]
+pub struct ManageAdmin<'info> {
+    #[account(mut, seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub admin: Signer<'info>,
+    #[account(seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Option<Account<'info, Governance>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeGovernance<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 4 + 32 * constants::MAX_GOVERNANCE_SIGNERS + 1 + 1,
+        seeds = [b"governance"],
+        bump
+    )]
+    pub governance: Account<'info, Governance>,
+    #[account(mut, seeds = [b"config"], bump, has_one = admin @ SaveFiError::Unauthorized)]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateGovernance<'info> {
+    #[account(mut, seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Account<'info, Governance>,
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct QueueParamChange<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 8 + 57 + 8 + 32 + 1,
+        seeds = [b"param_change", config.next_change_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub param_change: Account<'info, ParamChange>,
+    #[account(mut, seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(seeds = [b"fee_account"], bump)]
+    pub fee_account: Account<'info, FeeAccount>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Option<Account<'info, Governance>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteParamChange<'info> {
+    #[account(
+        mut,
+        close = proposer,
+        has_one = proposer,
+        seeds = [b"param_change", param_change.id.to_le_bytes().as_ref()],
+        bump = param_change.bump
+    )]
+    pub param_change: Account<'info, ParamChange>,
+    #[account(mut, seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(mut, seeds = [b"fee_account"], bump)]
+    pub fee_account: Account<'info, FeeAccount>,
+    /// CHECK: Receives the rent of the executed change, checked by has_one
+    #[account(mut)]
+    pub proposer: AccountInfo<'info>,
+    pub executor: Signer<'info>,
+    #[account(seeds = [b"role", role_grant.role.seed().as_ref(), executor.key().as_ref()], bump = role_grant.bump)]
+    pub role_grant: Account<'info, RoleGrant>,
+    /// Only needed to execute `ParamUpdate::MintLimits`
+    #[account(mut, seeds = [b"mint_config", mint_config.mint.as_ref()], bump = mint_config.bump)]
+    pub mint_config: Option<Account<'info, MintConfig>>,
+}
+
+#[derive(Accounts)]
+pub struct CancelParamChange<'info> {
+    #[account(
+        mut,
+        close = proposer,
+        has_one = proposer,
+        seeds = [b"param_change", param_change.id.to_le_bytes().as_ref()],
+        bump = param_change.bump
+    )]
+    pub param_change: Account<'info, ParamChange>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    /// CHECK: Receives the rent of the cancelled change, checked by has_one
+    #[account(mut)]
+    pub proposer: AccountInfo<'info>,
+    pub admin: Signer<'info>,
+    #[account(seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Option<Account<'info, Governance>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFee<'info> {
+    #[account(mut, seeds = [b"fee_account"], bump)]
+    pub fee_account: Account<'info, FeeAccount>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub admin: Signer<'info>,
+    #[account(seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Option<Account<'info, Governance>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateLimits<'info> {
+    #[account(mut, seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(seeds = [b"fee_account"], bump)]
+    pub fee_account: Account<'info, FeeAccount>,
+    pub admin: Signer<'info>,
+    #[account(seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Option<Account<'info, Governance>>,
+}
+
+#[derive(Accounts)]
+#[instruction(role: Role, member: Pubkey)]
+pub struct GrantRole<'info> {
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + 1 + 32 + 32 + 8 + 8 + 1,
+        seeds = [b"role", role.seed().as_ref(), member.as_ref()],
+        bump
+    )]
+    pub role_grant: Account<'info, RoleGrant>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Option<Account<'info, Governance>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeRole<'info> {
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"role", role_grant.role.seed().as_ref(), role_grant.member.as_ref()],
+        bump = role_grant.bump
+    )]
+    pub role_grant: Account<'info, RoleGrant>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Option<Account<'info, Governance>>,
+}
+
+#[derive(Accounts)]
+pub struct AddDexProgram<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 32 + 1 + 8 + 1,
+        seeds = [b"dex", program.key().as_ref()],
+        bump
+    )]
+    pub dex_program: Account<'info, DexProgram>,
+    /// CHECK: Only used for its address; must be an executable program
+    #[account(executable)]
+    pub program: AccountInfo<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Option<Account<'info, Governance>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDexProgram<'info> {
+    #[account(mut, seeds = [b"dex", dex_program.program_id.as_ref()], bump = dex_program.bump)]
+    pub dex_program: Account<'info, DexProgram>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub admin: Signer<'info>,
+    #[account(seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Option<Account<'info, Governance>>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveDexProgram<'info> {
+    #[account(mut, close = admin, seeds = [b"dex", dex_program.program_id.as_ref()], bump = dex_program.bump)]
+    pub dex_program: Account<'info, DexProgram>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Option<Account<'info, Governance>>,
+}
+
+#[derive(Accounts)]
+pub struct SetMintConfig<'info> {
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + 32 + 8 + 8 + 8 + 1 + 1,
+        seeds = [b"mint_config", mint.key().as_ref()],
+        bump
+    )]
+    pub mint_config: Account<'info, MintConfig>,
+    #[account(constraint = mint.key() != config.save_token_mint @ SaveFiError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(seeds = [b"fee_account"], bump)]
+    pub fee_account: Account<'info, FeeAccount>,
+    /// Created up front so deductions in this mint always have a fee destination
+    #[account(
+        init_if_needed,
+        payer = admin,
+        associated_token::mint = mint,
+        associated_token::authority = fee_account,
+        associated_token::token_program = token_program
+    )]
+    pub fee_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [b"governance"], bump = governance.bump)]
+    pub governance: Option<Account<'info, Governance>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        constraint = config.pending_admin == new_admin.key() @ SaveFiError::NotPendingAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    pub new_admin: Signer<'info>,
+}
+
+#[account]
+pub struct Vault {
+    pub owner: Pubkey,
+    pub savings_rate: u8,
+    pub lock_period_days: u8,
+    pub balance: u64,
+    /// Unlock time of the most recent SaveSOL save; withdrawals follow `tranches`
+    pub lock_until: i64,
+    pub is_active: bool,
+    pub next_payment_due: i64,
+    /// SaveSOL lots that have not matured yet
+    pub tranches: Vec<SavingsTranche>,
+    /// Mints whose `TokenSavings` balance is non-zero; the vault cannot close until it is zero
+    pub open_token_mints: u16,
+}
+
+impl Vault {
+    /// Returns the unlock time the lot was actually stored with.
+    pub fn add_tranche(&mut self, amount: u64, unlock_at: i64, now: i64) -> Result<i64> {
+        SavingsTranche::insert(&mut self.tranches, amount, unlock_at, now)
+    }
+
+    pub fn locked_balance(&self, now: i64) -> u64 {
+        SavingsTranche::locked(&self.tranches, now).min(self.balance)
+    }
+
+    pub fn unlocked_balance(&self, now: i64) -> u64 {
+        self.balance - self.locked_balance(now)
+    }
+
+    pub fn withdraw_unlocked(&mut self, amount: Option<u64>, now: i64) -> Result<u64> {
+        SavingsTranche::withdraw(&mut self.balance, &mut self.tranches, amount, now)
+    }
+}
+
+/// Token savings for one mint, locked in lots like the vault's SaveSOL. The tokens
+/// themselves sit in the vault's associated token account for the mint.
+#[account]
+pub struct TokenSavings {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub balance: u64,
+    pub tranches: Vec<SavingsTranche>,
+    pub bump: u8,
+}
+
+impl TokenSavings {
+    pub fn add_tranche(&mut self, amount: u64, unlock_at: i64, now: i64) -> Result<i64> {
+        SavingsTranche::insert(&mut self.tranches, amount, unlock_at, now)
+    }
+
+    pub fn withdraw_unlocked(&mut self, amount: Option<u64>, now: i64) -> Result<u64> {
+        SavingsTranche::withdraw(&mut self.balance, &mut self.tranches, amount, now)
+    }
+}
+
+/// A locked lot of savings. Lists of lots are kept sorted by `unlock_at`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SavingsTranche {
+    pub amount: u64,
+    pub unlock_at: i64,
+}
+
+impl SavingsTranche {
+    /// Records a newly locked lot. `unlock_at` is rounded up to the next
+    /// `TRANCHE_BUCKET_SECS` boundary and only merges into a lot with exactly that
+    /// unlock time, so merges can't chain. When the list is full the new lot joins
+    /// the latest lot that unlocks no later than it, so existing savings are never
+    /// locked for longer; if every lot unlocks later, the save is rejected.
+    /// Returns the unlock time the savings were stored with.
+    pub fn insert(tranches: &mut Vec<SavingsTranche>, amount: u64, unlock_at: i64, now: i64) -> Result<i64> {
+        Self::prune(tranches, now);
+        let bucket = unlock_at.div_euclid(constants::TRANCHE_BUCKET_SECS) * constants::TRANCHE_BUCKET_SECS;
+        let unlock_at = if bucket < unlock_at { bucket + constants::TRANCHE_BUCKET_SECS } else { bucket };
+        match tranches.binary_search_by_key(&unlock_at, |tranche| tranche.unlock_at) {
+            Ok(index) => tranches[index].amount += amount,
+            Err(index) if tranches.len() < constants::MAX_VAULT_TRANCHES => {
+                tranches.insert(index, SavingsTranche { amount, unlock_at });
+            }
+            Err(0) => return err!(SaveFiError::TooManyTranches),
+            Err(index) => {
+                let earlier = &mut tranches[index - 1];
+                earlier.amount += amount;
+                return Ok(earlier.unlock_at);
+            }
+        }
+        Ok(unlock_at)
+    }
+
+    /// Drops matured lots; their savings already count as unlocked.
+    pub fn prune(tranches: &mut Vec<SavingsTranche>, now: i64) {
+        tranches.retain(|tranche| tranche.unlock_at > now);
+    }
+
+    pub fn locked(tranches: &[SavingsTranche], now: i64) -> u64 {
+        tranches
+            .iter()
+            .filter(|tranche| tranche.unlock_at > now)
+            .map(|tranche| tranche.amount)
+            .sum()
+    }
+
+    /// Takes `amount` of matured savings, or all of them when `None`, out of `balance`.
+    pub fn withdraw(balance: &mut u64, tranches: &mut Vec<SavingsTranche>, amount: Option<u64>, now: i64) -> Result<u64> {
+        require!(*balance > 0, SaveFiError::EmptyVault);
+        let unlocked = *balance - Self::locked(tranches, now).min(*balance);
+        require!(unlocked > 0, SaveFiError::VaultLocked);
+        let amount = amount.unwrap_or(unlocked);
+        require!(amount > 0, SaveFiError::EmptyVault);
+        require!(amount <= unlocked, SaveFiError::InsufficientUnlockedBalance);
+        *balance -= amount;
+        Self::prune(tranches, now);
+        Ok(amount)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VaultBalances {
+    pub total: u64,
+    pub locked: u64,
+    pub unlocked: u64,
+    /// Earliest time more savings unlock, or zero when nothing is locked
+    pub next_unlock_at: i64,
+}
+
+#[account]
+pub struct Delegation {
+    pub owner: Pubkey,
+    pub id: u8,
+    pub delegated_amount: u64,
+    pub delegation_expiry: i64,
+    pub bump: u8,
+    pub window_start: i64,
+    pub window_spent: u64,
+    pub daily_limit: u64,
+    pub last_deduction_at: i64,
+    pub cooldown_secs: i64,
+    pub operator: Pubkey,
+    pub allowed_destinations: Vec<Pubkey>,
+    pub dex_only: bool,
+    /// SPL mint held in the escrow; the default key marks a native SOL delegation
+    pub mint: Pubkey,
+}
+
+impl Delegation {
+    pub fn open(&mut self, owner: Pubkey, id: u8, amount: u64, expiry: i64, bump: u8, mint: Pubkey) {
+        self.owner = owner;
+        self.id = id;
+        self.delegated_amount = amount;
+        self.delegation_expiry = expiry;
+        self.bump = bump;
+        self.window_start = 0;
+        self.window_spent = 0;
+        self.daily_limit = 0;
+        self.last_deduction_at = 0;
+        self.cooldown_secs = 0;
+        self.operator = owner;
+        self.allowed_destinations = Vec::new();
+        self.dex_only = false;
+        self.mint = mint;
+    }
+
+    pub fn is_native(&self) -> bool {
+        self.mint == Pubkey::default()
+    }
+
+    pub fn default_cooldown() -> i64 {
+        constants::DELEGATION_COOLDOWN_HOURS as i64 * 60 * 60
+    }
+
+    pub fn effective_cooldown(&self) -> i64 {
+        self.cooldown_secs.max(Self::default_cooldown())
+    }
+
+    /// An empty allowlist leaves the destination unrestricted.
+    pub fn check_destination(&self, destination: &AccountInfo) -> Result<()> {
+        self.check_allowed(&[*destination.key, *destination.owner])
+    }
+
+    /// Token destinations match on the token account itself or the wallet that owns it.
+    pub fn check_token_destination(&self, destination: &InterfaceAccount<TokenAccount>) -> Result<()> {
+        self.check_allowed(&[destination.key(), destination.owner])
+    }
+
+    fn check_allowed(&self, candidates: &[Pubkey]) -> Result<()> {
+        require!(
+            self.allowed_destinations.is_empty()
+                || candidates.iter().any(|candidate| self.allowed_destinations.contains(candidate)),
+            SaveFiError::DestinationNotAllowed
+        );
+        Ok(())
+    }
+    /// In DEX-only mode the destination's owner must be a registered, enabled DEX.
+    pub fn check_dex_program(&self, dex_program: Option<&DexProgram>) -> Result<()> {
+        if self.dex_only {
+            let dex_program = dex_program.ok_or(SaveFiError::DexNotRegistered)?;
+            require!(dex_program.enabled, SaveFiError::DexDisabled);
+        }
+        Ok(())
+    }
+
+
+    /// Enforces the minimum interval since the previous deduction and records `now`.
+    pub fn record_deduction(&mut self, now: i64) -> Result<()> {
+        let next_allowed = self.last_deduction_at.saturating_add(self.effective_cooldown());
+        if self.last_deduction_at != 0 && now < next_allowed {
+            msg!("Next deduction allowed in {} seconds", next_allowed - now);
+            return err!(SaveFiError::DeductionCooldown);
+        }
+        self.last_deduction_at = now;
+        Ok(())
+    }
+
+    /// `protocol_limit` is `DAILY_LIMIT_SOL` for SOL and the mint's limit for tokens.
+    pub fn effective_daily_limit(&self, protocol_limit: u64) -> u64 {
+        if self.daily_limit == 0 {
+            protocol_limit
+        } else {
+            self.daily_limit.min(protocol_limit)
+        }
+    }
+
+    /// Adds `amount` to the spend of the current 24-hour window, starting a new
+    /// window once the previous one has elapsed.
+    pub fn record_spend(&mut self, amount: u64, now: i64, protocol_limit: u64) -> Result<()> {
+        if now.saturating_sub(self.window_start) >= 24 * 60 * 60 {
+            self.window_start = now;
+            self.window_spent = 0;
+        }
+        let spent = self.window_spent.checked_add(amount).ok_or(SaveFiError::DailyLimitExceeded)?;
+        require!(spent <= self.effective_daily_limit(protocol_limit), SaveFiError::DailyLimitExceeded);
+        self.window_spent = spent;
+        Ok(())
+    }
+}
+
+/// Lists the ids of a user's open delegations so clients can enumerate them.
+#[account]
+pub struct DelegationRegistry {
+    pub owner: Pubkey,
+    pub ids: Vec<u8>,
+    pub bump: u8,
+}
+
+impl DelegationRegistry {
+    pub fn add(&mut self, id: u8) -> Result<()> {
+        require!(!self.ids.contains(&id), SaveFiError::DelegationIdInUse);
+        require!(self.ids.len() < constants::MAX_DELEGATIONS_PER_USER, SaveFiError::TooManyDelegations);
+        self.ids.push(id);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: u8) {
+        self.ids.retain(|existing| *existing != id);
+    }
+}
+
+/// Records a processed `auto_deduct` so a retried trade id is rejected.
+#[account]
+pub struct TradeReceipt {
+    pub delegation: Pubkey,
+    pub trade_id: [u8; 16],
+    pub trade_amount: u64,
+    pub processed_at: i64,
+    pub payer: Pubkey,
+    pub bump: u8,
+}
+
+impl TradeReceipt {
+    /// A receipt that already carries a timestamp means this trade id was processed.
+    pub fn record(
+        &mut self,
+        delegation: Pubkey,
+        trade_id: [u8; 16],
+        trade_amount: u64,
+        now: i64,
+        payer: Pubkey,
+        bump: u8,
+    ) -> Result<()> {
+        require!(self.processed_at == 0, SaveFiError::DuplicateTrade);
+        self.delegation = delegation;
+        self.trade_id = trade_id;
+        self.trade_amount = trade_amount;
+        self.processed_at = now;
+        self.payer = payer;
+        self.bump = bump;
+        Ok(())
+    }
+
+    /// Receipts must outlive the window in which a trade id could be retried.
+    pub fn check_retention_elapsed(&self, now: i64) -> Result<()> {
+        require!(
+            now >= self.processed_at + constants::TRADE_RECEIPT_RETENTION_SECS,
+            SaveFiError::ReceiptRetention
+        );
+        Ok(())
+    }
+}
+
+/// Per-mint limits for token delegations, in the mint's base units.
+#[account]
+pub struct MintConfig {
+    pub mint: Pubkey,
+    pub min_delegation: u64,
+    pub max_delegation: u64,
+    pub daily_limit: u64,
+    pub enabled: bool,
+    pub bump: u8,
+}
+
+impl MintConfig {
+    pub fn check_limits(min_delegation: u64, max_delegation: u64, daily_limit: u64) -> Result<()> {
+        require!(
+            min_delegation > 0 && min_delegation <= max_delegation && max_delegation <= daily_limit,
+            SaveFiError::InvalidDelegationLimits
+        );
+        Ok(())
+    }
+
+    /// A new mint takes any valid limits; a configured one may only tighten them without
+    /// the timelock.
+    pub fn check_immediate_update(&self, min_delegation: u64, max_delegation: u64, daily_limit: u64) -> Result<()> {
+        require!(
+            self.mint == Pubkey::default()
+                || (min_delegation >= self.min_delegation
+                    && max_delegation <= self.max_delegation
+                    && daily_limit <= self.daily_limit),
+            SaveFiError::LimitChangeRequiresTimelock
+        );
+        Ok(())
+    }
+}
+
+/// Holds the lamports backing all outstanding SaveSOL.
+#[account]
+pub struct SavingsReserve {
+    pub total_saved: u64,
+    pub bump: u8,
+}
+
+impl SavingsReserve {
+    /// Moves `amount` lamports from a program-owned `from` account into the reserve.
+    pub fn deposit(&mut self, reserve: &AccountInfo, from: &AccountInfo, amount: u64) -> Result<()> {
+        self.total_saved += amount;
+        from.sub_lamports(amount)?;
+        reserve.add_lamports(amount)?;
+        Ok(())
+    }
+
+    /// Pays `amount` lamports out of the reserve for the same amount of burned SaveSOL.
+    pub fn redeem(&mut self, reserve: &AccountInfo, to: &AccountInfo, amount: u64) -> Result<()> {
+        self.total_saved = self.total_saved.checked_sub(amount).ok_or(SaveFiError::InsufficientReserve)?;
+        reserve.sub_lamports(amount)?;
+        to.add_lamports(amount)?;
+        Ok(())
+    }
+}
+
+#[account]
+pub struct MintAuthority {
+    pub bump: u8,
+}
+
+#[account]
+pub struct FeeAccount {
+    pub balance: u64,
+    pub fee_rate: u8,
+    pub last_collection_time: i64,
+    pub collection_cooldown: i64,
+    pub previous_fee_rate: u8,
+    pub fee_rate_updated_at: i64,
+}
+
+impl FeeAccount {
+    /// Applies a fee rate change, limited to `MAX_FEE_RATE_STEP` per update. An increase
+    /// must also wait `min_interval` since the last change, so queued steps cannot be chained.
+    /// Callers validate `new_rate` against the configured maximum.
+    pub fn set_fee_rate(&mut self, new_rate: u8, now: i64, min_interval: i64) -> Result<()> {
+        self.check_fee_step(new_rate)?;
+        require!(
+            new_rate <= self.fee_rate || now.saturating_sub(self.fee_rate_updated_at) >= min_interval,
+            SaveFiError::FeeRateChangeTooSoon
+        );
+        let previous_rate = self.fee_rate;
+        self.previous_fee_rate = previous_rate;
+        self.fee_rate = new_rate;
+        self.fee_rate_updated_at = now;
+
+        emit!(FeeRateUpdated {
+            previous_rate,
+            new_rate,
+            timestamp: now,
+        });
+        Ok(())
+    }
 
-#[account]
-pub struct FeeAccount {
-    pub authority: Pubkey,
-    pub balance: u64,
-    pub fee_rate: u8,
+    pub fn check_fee_step(&self, new_rate: u8) -> Result<()> {
+        require!(
+            new_rate.abs_diff(self.fee_rate) <= constants::MAX_FEE_RATE_STEP,
+            SaveFiError::FeeRateStepTooLarge
+        );
+        Ok(())
+    }
 }
 
 #[account]
@@ -441,8 +2500,286 @@ pub struct ProxyAccount {
 #[account]
 pub struct ProtocolConfig {
     pub admin: Pubkey,
-    pub paused: bool,
+    pub pause_flags: u16,
     pub save_token_mint: Pubkey,
+    pub pause_reason: u8,
+    pub pause_updated_at: i64,
+    pub pending_admin: Pubkey,
+    pub multisig_enabled: bool,
+    pub subscription_fee: u64,
+    pub min_delegation: u64,
+    pub max_delegation: u64,
+    pub timelock_delay: i64,
+    pub next_change_id: u64,
+    pub limits: ProtocolLimits,
+    /// Bumped on every admin handover; role grants from earlier epochs are void
+    pub admin_epoch: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolLimits {
+    pub min_lock_days: u8,
+    pub max_lock_days: u8,
+    pub min_save_rate: u8,
+    pub max_save_rate: u8,
+    pub max_fee_rate: u8,
+    pub subscription_period_days: u8,
+}
+
+impl Default for ProtocolLimits {
+    fn default() -> Self {
+        Self {
+            min_lock_days: constants::MIN_LOCK_DAYS,
+            max_lock_days: constants::MAX_LOCK_DAYS,
+            min_save_rate: constants::MIN_SAVE_RATE,
+            max_save_rate: constants::MAX_SAVE_RATE,
+            max_fee_rate: constants::MAX_FEE_RATE,
+            subscription_period_days: constants::SUBSCRIPTION_PERIOD_DAYS,
+        }
+    }
+}
+
+impl ProtocolLimits {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.min_lock_days >= 1 && self.min_lock_days <= self.max_lock_days && self.max_lock_days <= constants::LOCK_DAYS_CAP,
+            SaveFiError::InvalidLockPeriod
+        );
+        require!(
+            self.min_save_rate >= 1 && self.min_save_rate <= self.max_save_rate && self.max_save_rate <= constants::SAVE_RATE_CAP,
+            SaveFiError::InvalidSaveRate
+        );
+        require!(self.max_fee_rate <= constants::FEE_RATE_CAP, SaveFiError::InvalidFeeRate);
+        require!(
+            (1..=constants::SUBSCRIPTION_PERIOD_DAYS_CAP).contains(&self.subscription_period_days),
+            SaveFiError::InvalidSubscriptionPeriod
+        );
+        Ok(())
+    }
+
+    pub fn check_save_rate(&self, rate: u8) -> Result<()> {
+        require!((self.min_save_rate..=self.max_save_rate).contains(&rate), SaveFiError::InvalidSaveRate);
+        Ok(())
+    }
+
+    pub fn check_lock_days(&self, days: u8) -> Result<()> {
+        require!((self.min_lock_days..=self.max_lock_days).contains(&days), SaveFiError::InvalidLockPeriod);
+        Ok(())
+    }
+}
+
+impl ProtocolConfig {
+    pub fn is_paused(&self, flag: u16) -> bool {
+        self.pause_flags & flag != 0
+    }
+
+    /// Checks that a privileged config change is approved: by the admin key or a
+    /// super-admin (its role grant passed as a remaining account), or by the
+    /// governance threshold (signers passed as remaining accounts) in multisig mode.
+    pub fn authorize_admin(
+        &self,
+        admin: &AccountInfo,
+        governance: Option<&Governance>,
+        remaining_accounts: &[AccountInfo],
+    ) -> Result<()> {
+        if !self.multisig_enabled {
+            if admin.key() == self.admin {
+                return Ok(());
+            }
+            return self.authorize_super_admin(admin, remaining_accounts);
+        }
+        let governance = governance.ok_or(SaveFiError::GovernanceRequired)?;
+        governance.verify_approvals(admin, remaining_accounts)
+    }
+
+    fn authorize_super_admin(&self, admin: &AccountInfo, remaining_accounts: &[AccountInfo]) -> Result<()> {
+        require!(admin.is_signer, SaveFiError::Unauthorized);
+        let (grant_key, _) = Pubkey::find_program_address(
+            &[b"role", Role::SuperAdmin.seed().as_ref(), admin.key.as_ref()],
+            &crate::ID,
+        );
+        let grant_info = remaining_accounts
+            .iter()
+            .find(|account| *account.key == grant_key)
+            .ok_or(SaveFiError::Unauthorized)?;
+        require_keys_eq!(*grant_info.owner, crate::ID, SaveFiError::Unauthorized);
+        let grant = RoleGrant::try_deserialize(&mut &grant_info.try_borrow_data()?[..])?;
+        grant.require_role(Role::SuperAdmin, self)
+    }
+
+    fn set_pause_flags(&mut self, flags: u16, reason: u8, authority: Pubkey) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        self.pause_flags = flags;
+        self.pause_reason = reason;
+        self.pause_updated_at = now;
+        emit!(ProtocolPauseChanged {
+            authority,
+            pause_flags: flags,
+            reason,
+            timestamp: now,
+        });
+        Ok(())
+    }
+}
+
+#[account]
+pub struct Governance {
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub bump: u8,
+}
+
+impl Governance {
+    pub fn set_signers(&mut self, signers: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        require!(
+            !signers.is_empty() && signers.len() <= constants::MAX_GOVERNANCE_SIGNERS,
+            SaveFiError::InvalidGovernanceSigners
+        );
+        for (i, signer) in signers.iter().enumerate() {
+            require!(
+                *signer != Pubkey::default() && !signers[..i].contains(signer),
+                SaveFiError::InvalidGovernanceSigners
+            );
+        }
+        require!(
+            threshold > 0 && threshold as usize <= signers.len(),
+            SaveFiError::InvalidGovernanceThreshold
+        );
+        self.signers = signers;
+        self.threshold = threshold;
+        Ok(())
+    }
+
+    /// Counts distinct governance signers among `signer` and `remaining_accounts`.
+    pub fn verify_approvals(&self, signer: &AccountInfo, remaining_accounts: &[AccountInfo]) -> Result<()> {
+        let candidates = std::iter::once((signer.key, signer.is_signer))
+            .chain(remaining_accounts.iter().map(|account| (account.key, account.is_signer)));
+        let mut approvals: Vec<Pubkey> = Vec::with_capacity(self.signers.len());
+        for (key, is_signer) in candidates {
+            if is_signer && self.signers.contains(key) && !approvals.contains(key) {
+                approvals.push(*key);
+            }
+        }
+        require!(approvals.len() >= self.threshold as usize, SaveFiError::InsufficientApprovals);
+        Ok(())
+    }
+}
+
+#[account]
+pub struct ParamChange {
+    pub id: u64,
+    pub update: ParamUpdate,
+    pub eta: i64,
+    pub proposer: Pubkey,
+    pub bump: u8,
+}
+
+impl ParamChange {
+    /// A change executes from its eta until the grace period runs out. Executing or
+    /// cancelling closes the account, and ids are never reused, so it runs at most once.
+    pub fn check_executable(&self, now: i64) -> Result<()> {
+        require!(now >= self.eta, SaveFiError::TimelockNotElapsed);
+        require!(
+            now <= self.eta.saturating_add(constants::TIMELOCK_GRACE_PERIOD_SECS),
+            SaveFiError::ParamChangeStale
+        );
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ParamUpdate {
+    FeeRate(u8),
+    SubscriptionFee(u64),
+    DelegationLimits { min: u64, max: u64 },
+    TimelockDelay(i64),
+    SubscriptionPeriod(u8),
+    MaxFeeRate(u8),
+    /// Per-mint token delegation limits, in the mint's base units
+    MintLimits { mint: Pubkey, min: u64, max: u64, daily_limit: u64 },
+}
+
+impl ParamUpdate {
+    /// Checked when the change is queued and again when it executes.
+    pub fn validate(&self, config: &ProtocolConfig, fee_account: &FeeAccount) -> Result<()> {
+        match *self {
+            ParamUpdate::FeeRate(rate) => {
+                require!(rate <= config.limits.max_fee_rate, SaveFiError::InvalidFeeRate);
+                fee_account.check_fee_step(rate)?;
+            }
+            ParamUpdate::SubscriptionFee(fee) => {
+                require!(fee <= constants::MAX_SUBSCRIPTION_FEE_SOL, SaveFiError::InvalidSubscriptionFee);
+            }
+            ParamUpdate::DelegationLimits { min, max } => {
+                require!(
+                    min > 0 && min <= max && max <= constants::DELEGATION_LIMIT_CAP_SOL,
+                    SaveFiError::InvalidDelegationLimits
+                );
+            }
+            ParamUpdate::TimelockDelay(delay) => {
+                require!(
+                    (constants::MIN_TIMELOCK_DELAY_SECS..=constants::MAX_TIMELOCK_DELAY_SECS).contains(&delay),
+                    SaveFiError::InvalidTimelockDelay
+                );
+            }
+            ParamUpdate::SubscriptionPeriod(days) => {
+                require!(
+                    (1..=constants::SUBSCRIPTION_PERIOD_DAYS_CAP).contains(&days),
+                    SaveFiError::InvalidSubscriptionPeriod
+                );
+            }
+            ParamUpdate::MaxFeeRate(rate) => {
+                require!(rate <= constants::FEE_RATE_CAP, SaveFiError::InvalidFeeRate);
+            }
+            ParamUpdate::MintLimits { min, max, daily_limit, .. } => {
+                MintConfig::check_limits(min, max, daily_limit)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[account]
+pub struct RoleGrant {
+    pub role: Role,
+    pub member: Pubkey,
+    pub granted_by: Pubkey,
+    pub granted_at: i64,
+    /// Admin epoch the grant was issued in
+    pub epoch: u64,
+    pub bump: u8,
+}
+
+impl RoleGrant {
+    /// A super-admin grant satisfies every role check. Grants issued before the
+    /// last admin handover no longer count.
+    pub fn require_role(&self, role: Role, config: &ProtocolConfig) -> Result<()> {
+        require!(self.epoch == config.admin_epoch, SaveFiError::StaleRoleGrant);
+        require!(self.role == role || self.role == Role::SuperAdmin, SaveFiError::MissingRole);
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    SuperAdmin,
+    Pauser,
+    FeeCollector,
+    Keeper,
+}
+
+impl Role {
+    pub fn seed(&self) -> [u8; 1] {
+        [*self as u8]
+    }
+}
+
+#[account]
+pub struct DexProgram {
+    pub program_id: Pubkey,
+    pub enabled: bool,
+    pub updated_at: i64,
+    pub bump: u8,
 }
 
 #[account]
@@ -450,13 +2787,122 @@ pub struct ReentrancyGuard {
     pub locked: bool,
 }
 
+#[event]
+pub struct ProtocolPauseChanged {
+    pub authority: Pubkey,
+    pub pause_flags: u16,
+    pub reason: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminProposed {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminProposalCancelled {
+    pub admin: Pubkey,
+    pub cancelled_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminTransferred {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GovernanceSignersUpdated {
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ParamChangeQueued {
+    pub id: u64,
+    pub update: ParamUpdate,
+    pub eta: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ParamChangeExecuted {
+    pub id: u64,
+    pub update: ParamUpdate,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ParamChangeCancelled {
+    pub id: u64,
+    pub update: ParamUpdate,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolLimitsUpdated {
+    pub limits: ProtocolLimits,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeRateUpdated {
+    pub previous_rate: u8,
+    pub new_rate: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RoleGranted {
+    pub role: Role,
+    pub member: Pubkey,
+    pub granted_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RoleRevoked {
+    pub role: Role,
+    pub member: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DexProgramUpdated {
+    pub program_id: Pubkey,
+    pub enabled: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DexProgramRemoved {
+    pub program_id: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MintConfigUpdated {
+    pub mint: Pubkey,
+    pub min_delegation: u64,
+    pub max_delegation: u64,
+    pub daily_limit: u64,
+    pub enabled: bool,
+    pub timestamp: i64,
+}
+
 #[error_code]
 pub enum SaveFiError {
-    #[msg("Save rate must be between 1 and 20")]
+    #[msg("Save rate is outside the allowed range")]
     InvalidSaveRate,
-    #[msg("Fee rate must be between 0 and 5")]
+    #[msg("Fee rate is outside the allowed range")]
     InvalidFeeRate,
-    #[msg("Lock period must be between 1 and 30 days")]
+    #[msg("Lock period is outside the allowed range")]
     InvalidLockPeriod,
     #[msg("Unauthorized access")]
     Unauthorized,
@@ -482,4 +2928,786 @@ pub enum SaveFiError {
     DelegationExpired,
     #[msg("Invalid SaveSOL mint")]
     InvalidMint,
-}
\ No newline at end of file
+    #[msg("Fee collection cooldown period not elapsed")]
+    CollectionCooldown,
+    #[msg("Requested amount exceeds collectable fees")]
+    InsufficientFeeBalance,
+    #[msg("Collection cooldown out of range")]
+    InvalidCooldown,
+    #[msg("Protocol is already paused")]
+    AlreadyPaused,
+    #[msg("Protocol is not paused")]
+    NotPaused,
+    #[msg("Invalid pause flags")]
+    InvalidPauseFlags,
+    #[msg("Vault initialization is paused")]
+    InitializeVaultPaused,
+    #[msg("Delegating funds is paused")]
+    DelegateFundsPaused,
+    #[msg("Automatic deductions are paused")]
+    AutoDeductPaused,
+    #[msg("Vault updates are paused")]
+    UpdateVaultPaused,
+    #[msg("Withdrawals are paused")]
+    WithdrawPaused,
+    #[msg("Subscription renewals are paused")]
+    RenewSubscriptionPaused,
+    #[msg("Revoking delegations is paused")]
+    RevokeDelegationPaused,
+    #[msg("Invalid admin")]
+    InvalidAdmin,
+    #[msg("No admin handover pending")]
+    NoPendingAdmin,
+    #[msg("Signer is not the pending admin")]
+    NotPendingAdmin,
+    #[msg("Multisig governance is already enabled")]
+    MultisigAlreadyEnabled,
+    #[msg("Governance account required in multisig mode")]
+    GovernanceRequired,
+    #[msg("Invalid governance signer set")]
+    InvalidGovernanceSigners,
+    #[msg("Invalid governance threshold")]
+    InvalidGovernanceThreshold,
+    #[msg("Not enough governance signers approved")]
+    InsufficientApprovals,
+    #[msg("Parameter change eta is earlier than the timelock delay allows")]
+    EtaTooEarly,
+    #[msg("Parameter change timelock has not elapsed")]
+    TimelockNotElapsed,
+    #[msg("Parameter change grace period has expired")]
+    ParamChangeStale,
+    #[msg("Invalid subscription fee")]
+    InvalidSubscriptionFee,
+    #[msg("Invalid delegation limits")]
+    InvalidDelegationLimits,
+    #[msg("Invalid timelock delay")]
+    InvalidTimelockDelay,
+    #[msg("Invalid subscription period")]
+    InvalidSubscriptionPeriod,
+    #[msg("Fee rate change exceeds the maximum step")]
+    FeeRateStepTooLarge,
+    #[msg("Fee increases must go through the timelock")]
+    FeeIncreaseRequiresTimelock,
+    #[msg("Signer does not hold the required role")]
+    MissingRole,
+    #[msg("Daily spending limit exceeded")]
+    DailyLimitExceeded,
+    #[msg("Daily limit exceeds the protocol limit")]
+    InvalidDailyLimit,
+    #[msg("Deduction cooldown has not elapsed")]
+    DeductionCooldown,
+    #[msg("Delegation still holds funds")]
+    DelegationNotEmpty,
+    #[msg("Delegation has not expired yet")]
+    DelegationStillActive,
+    #[msg("Vault still holds savings")]
+    VaultNotEmpty,
+    #[msg("No operator registered for this delegation")]
+    OperatorNotSet,
+    #[msg("Signer is not the delegation operator")]
+    UnauthorizedOperator,
+    #[msg("Destination is not on the delegation allowlist")]
+    DestinationNotAllowed,
+    #[msg("Destination allowlist is full")]
+    AllowlistFull,
+    #[msg("Destination is already on the allowlist")]
+    DestinationAlreadyAllowed,
+    #[msg("Destination is not on the allowlist")]
+    DestinationNotFound,
+    #[msg("Destination is not owned by a registered DEX program")]
+    DexNotRegistered,
+    #[msg("DEX program is disabled")]
+    DexDisabled,
+    #[msg("Delegation id is already in use")]
+    DelegationIdInUse,
+    #[msg("Too many open delegations")]
+    TooManyDelegations,
+    #[msg("Timestamp is ahead of the cluster clock")]
+    InvalidTimestamp,
+    #[msg("Timestamp is too far behind the cluster clock")]
+    StaleTimestamp,
+    #[msg("Trade id has already been processed")]
+    DuplicateTrade,
+    #[msg("Trade receipt is still within its retention period")]
+    ReceiptRetention,
+    #[msg("Delegation balance would fall below rent exemption")]
+    InsufficientDelegationBalance,
+    #[msg("Instruction only supports native SOL delegations")]
+    NotNativeDelegation,
+    #[msg("Mint is not enabled for token delegations")]
+    MintDisabled,
+    #[msg("DEX-only mode is only supported for SOL delegations")]
+    DexOnlyUnsupported,
+    #[msg("Mint uses a Token-2022 extension that is not supported")]
+    UnsupportedMintExtension,
+    #[msg("Savings reserve cannot cover the redemption")]
+    InsufficientReserve,
+    #[msg("Withdrawal exceeds the unlocked vault balance")]
+    InsufficientUnlockedBalance,
+    #[msg("Role was granted under a previous admin")]
+    StaleRoleGrant,
+    #[msg("Subscription period and fee cap increases must go through the timelock")]
+    LimitChangeRequiresTimelock,
+    #[msg("No fees to collect")]
+    NoFeesToCollect,
+    #[msg("Fee rate was raised too recently")]
+    FeeRateChangeTooSoon,
+    #[msg("Too many savings lots are still locked")]
+    TooManyTranches,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60;
+    const START: i64 = 1_700_000_000;
+
+    fn vault(lock_period_days: u8) -> Vault {
+        Vault {
+            owner: Pubkey::default(),
+            savings_rate: 10,
+            lock_period_days,
+            balance: 0,
+            lock_until: 0,
+            is_active: true,
+            next_payment_due: 0,
+            tranches: Vec::new(),
+            open_token_mints: 0,
+        }
+    }
+
+    fn save(vault: &mut Vault, amount: u64, now: i64) {
+        vault.balance += amount;
+        vault.lock_until = vault.add_tranche(amount, now + vault.lock_period_days as i64 * DAY, now).unwrap();
+    }
+
+    fn delegation() -> Delegation {
+        let mut delegation = Delegation {
+            owner: Pubkey::default(),
+            id: 0,
+            delegated_amount: 0,
+            delegation_expiry: 0,
+            bump: 0,
+            window_start: 0,
+            window_spent: 0,
+            daily_limit: 0,
+            last_deduction_at: 0,
+            cooldown_secs: 0,
+            operator: Pubkey::default(),
+            allowed_destinations: Vec::new(),
+            dex_only: false,
+            mint: Pubkey::default(),
+        };
+        delegation.open(Pubkey::new_unique(), 0, 100_000, START + 30 * DAY, 255, Pubkey::default());
+        delegation
+    }
+
+    fn fee_account(fee_rate: u8) -> FeeAccount {
+        FeeAccount {
+            balance: 0,
+            fee_rate,
+            last_collection_time: 0,
+            collection_cooldown: 0,
+            previous_fee_rate: fee_rate,
+            fee_rate_updated_at: START,
+        }
+    }
+
+    fn config() -> ProtocolConfig {
+        ProtocolConfig {
+            admin: Pubkey::new_unique(),
+            pause_flags: 0,
+            save_token_mint: Pubkey::default(),
+            pause_reason: 0,
+            pause_updated_at: 0,
+            pending_admin: Pubkey::default(),
+            multisig_enabled: false,
+            subscription_fee: 0,
+            min_delegation: 0,
+            max_delegation: 0,
+            timelock_delay: 0,
+            next_change_id: 0,
+            limits: ProtocolLimits::default(),
+            admin_epoch: 0,
+        }
+    }
+
+    #[test]
+    fn hourly_saves_mature_on_schedule() {
+        let mut vault = vault(7);
+        let mut saves = Vec::new();
+        for hour in 0..30 * 24 {
+            let now = START + hour * 60 * 60;
+            save(&mut vault, 1_000, now);
+            saves.push(now);
+
+            // Every save unlocks less than a bucket after its own lock period
+            let expected_unlocked = saves.iter().filter(|saved_at| **saved_at + 8 * DAY <= now).count() as u64 * 1_000;
+            let locked_by_schedule = saves.iter().filter(|saved_at| **saved_at + 7 * DAY > now).count() as u64 * 1_000;
+            assert!(vault.unlocked_balance(now) >= expected_unlocked);
+            assert!(vault.locked_balance(now) >= locked_by_schedule);
+            assert!(vault.tranches.len() <= constants::MAX_VAULT_TRANCHES);
+        }
+        assert!(vault.unlocked_balance(START + 30 * DAY) >= 22 * 24 * 1_000);
+    }
+
+    #[test]
+    fn full_list_folds_into_the_newest_earlier_lot() {
+        let mut vault = vault(180);
+        for day in 0..constants::MAX_VAULT_TRANCHES as i64 + 5 {
+            save(&mut vault, 1_000, START + day * DAY);
+        }
+        assert_eq!(vault.tranches.len(), constants::MAX_VAULT_TRANCHES);
+        assert_eq!(vault.locked_balance(START + 40 * DAY), vault.balance);
+
+        // Overflow joins the newest lot; older lots keep their unlock times
+        let first_unlock = START + 180 * DAY;
+        let newest = vault.tranches.last().unwrap();
+        assert_eq!(vault.tranches[0].amount, 1_000);
+        assert!((0..DAY).contains(&(vault.tranches[0].unlock_at - first_unlock)));
+        assert_eq!(newest.amount, 6_000);
+        assert_eq!(vault.lock_until, newest.unlock_at);
+        assert!(vault.tranches.windows(2).all(|pair| pair[0].unlock_at < pair[1].unlock_at));
+    }
+
+    #[test]
+    fn full_list_rejects_a_lot_that_unlocks_before_all_others() {
+        let mut vault = vault(180);
+        for day in 0..constants::MAX_VAULT_TRANCHES as i64 {
+            save(&mut vault, 1_000, START + day * DAY);
+        }
+        let now = START + constants::MAX_VAULT_TRANCHES as i64 * DAY;
+        assert_eq!(
+            vault.add_tranche(1_000, now + DAY, now).unwrap_err(),
+            SaveFiError::TooManyTranches.into()
+        );
+        assert_eq!(vault.tranches.len(), constants::MAX_VAULT_TRANCHES);
+    }
+
+    #[test]
+    fn savings_unlock_at_lock_until() {
+        let mut vault = vault(7);
+        save(&mut vault, 2_000, START);
+        assert!(vault.lock_until >= START + 7 * DAY);
+        assert!(vault.withdraw_unlocked(None, vault.lock_until - 1).is_err());
+        assert_eq!(vault.withdraw_unlocked(None, vault.lock_until).unwrap(), 2_000);
+    }
+
+    #[test]
+    fn partial_withdraw_takes_only_matured_savings() {
+        let mut vault = vault(1);
+        save(&mut vault, 5_000, START);
+        save(&mut vault, 3_000, START + 3 * DAY);
+        let now = START + 3 * DAY + 60;
+
+        assert_eq!(vault.unlocked_balance(now), 5_000);
+        assert!(vault.withdraw_unlocked(Some(5_001), now).is_err());
+        assert_eq!(vault.withdraw_unlocked(Some(2_000), now).unwrap(), 2_000);
+        assert_eq!(vault.balance, 6_000);
+        assert_eq!(vault.locked_balance(now), 3_000);
+        assert_eq!(vault.withdraw_unlocked(None, now).unwrap(), 3_000);
+        assert!(vault.withdraw_unlocked(None, now).is_err());
+        assert_eq!(vault.unlocked_balance(START + 6 * DAY), 3_000);
+    }
+    #[test]
+    fn token_savings_lock_independently_of_the_vault() {
+        let mut savings = TokenSavings {
+            vault: Pubkey::default(),
+            mint: Pubkey::default(),
+            balance: 0,
+            tranches: Vec::new(),
+            bump: 0,
+        };
+        savings.balance += 4_000;
+        savings.add_tranche(4_000, START + 3 * DAY, START).unwrap();
+        savings.balance += 1_000;
+        savings.add_tranche(1_000, START + 2 * DAY, START + DAY).unwrap();
+
+        assert!(savings.withdraw_unlocked(None, START + DAY + 60).is_err());
+        let now = START + 3 * DAY + 60;
+        assert_eq!(savings.withdraw_unlocked(None, now).unwrap(), 1_000);
+        assert_eq!(savings.balance, 4_000);
+        assert!(savings.withdraw_unlocked(Some(1), now).is_err());
+        assert_eq!(savings.withdraw_unlocked(None, START + 4 * DAY).unwrap(), 4_000);
+    }
+
+    #[test]
+    fn admin_handover_voids_existing_grants() {
+        let mut config = config();
+        let mut grant = RoleGrant {
+            role: Role::Pauser,
+            member: Pubkey::new_unique(),
+            granted_by: config.admin,
+            granted_at: START,
+            epoch: config.admin_epoch,
+            bump: 0,
+        };
+        assert!(grant.require_role(Role::Pauser, &config).is_ok());
+        assert!(grant.require_role(Role::Keeper, &config).is_err());
+
+        config.admin_epoch += 1;
+        assert!(grant.require_role(Role::Pauser, &config).is_err());
+        grant.epoch = config.admin_epoch;
+        assert!(grant.require_role(Role::Pauser, &config).is_ok());
+    }
+
+    #[test]
+    fn super_admin_grant_satisfies_admin_checks() {
+        let mut config = config();
+        let member = Pubkey::new_unique();
+        let (grant_key, _) =
+            Pubkey::find_program_address(&[b"role", Role::SuperAdmin.seed().as_ref(), member.as_ref()], &crate::ID);
+        let grant = RoleGrant {
+            role: Role::SuperAdmin,
+            member,
+            granted_by: config.admin,
+            granted_at: START,
+            epoch: config.admin_epoch,
+            bump: 0,
+        };
+        let mut data = Vec::new();
+        grant.try_serialize(&mut data).unwrap();
+
+        let (mut signer_lamports, mut grant_lamports) = (0, 0);
+        let mut signer_data = [];
+        let signer = AccountInfo::new(&member, true, false, &mut signer_lamports, &mut signer_data, &member, false, 0);
+        let grant_info = AccountInfo::new(&grant_key, false, false, &mut grant_lamports, &mut data, &crate::ID, false, 0);
+
+        assert!(config.authorize_admin(&signer, None, &[]).is_err());
+        assert!(config.authorize_admin(&signer, None, std::slice::from_ref(&grant_info)).is_ok());
+        config.admin_epoch += 1;
+        assert!(config.authorize_admin(&signer, None, &[grant_info]).is_err());
+    }
+
+    #[test]
+    fn timelocked_limit_updates_are_bounded() {
+        let (config, fee_account) = (config(), fee_account(1));
+        assert!(ParamUpdate::SubscriptionPeriod(0).validate(&config, &fee_account).is_err());
+        assert!(ParamUpdate::SubscriptionPeriod(constants::SUBSCRIPTION_PERIOD_DAYS_CAP)
+            .validate(&config, &fee_account)
+            .is_ok());
+        assert!(ParamUpdate::MaxFeeRate(constants::FEE_RATE_CAP).validate(&config, &fee_account).is_ok());
+        assert!(ParamUpdate::MaxFeeRate(constants::FEE_RATE_CAP + 1).validate(&config, &fee_account).is_err());
+    }
+
+    #[test]
+    fn fee_rate_steps_are_checked_at_queue_time() {
+        let (config, fee_account) = (config(), fee_account(1));
+        assert!(ParamUpdate::FeeRate(2).validate(&config, &fee_account).is_ok());
+        assert_eq!(
+            ParamUpdate::FeeRate(3).validate(&config, &fee_account).unwrap_err(),
+            SaveFiError::FeeRateStepTooLarge.into()
+        );
+    }
+
+    #[test]
+    fn chained_fee_increases_are_rejected() {
+        let delay = constants::TIMELOCK_DELAY_SECS;
+        let mut fee_account = fee_account(1);
+        let now = START + delay;
+        fee_account.set_fee_rate(2, now, delay).unwrap();
+        assert_eq!(
+            fee_account.set_fee_rate(3, now, delay).unwrap_err(),
+            SaveFiError::FeeRateChangeTooSoon.into()
+        );
+        assert!(fee_account.set_fee_rate(1, now, delay).is_ok());
+        assert!(fee_account.set_fee_rate(2, now + delay - 1, delay).is_err());
+        assert!(fee_account.set_fee_rate(2, now + delay, delay).is_ok());
+        assert_eq!(fee_account.fee_rate, 2);
+    }
+
+    #[test]
+    fn trade_receipts_reject_replays_until_retention_ends() {
+        let mut receipt = TradeReceipt {
+            delegation: Pubkey::default(),
+            trade_id: [0; 16],
+            trade_amount: 0,
+            processed_at: 0,
+            payer: Pubkey::default(),
+            bump: 0,
+        };
+        let (delegation, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        receipt.record(delegation, [7; 16], 1_000, START, payer, 255).unwrap();
+        assert_eq!(
+            receipt.record(delegation, [7; 16], 1_000, START + 60, payer, 255).unwrap_err(),
+            SaveFiError::DuplicateTrade.into()
+        );
+        assert_eq!(receipt.processed_at, START);
+
+        let retention = constants::TRADE_RECEIPT_RETENTION_SECS;
+        assert_eq!(
+            receipt.check_retention_elapsed(START + retention - 1).unwrap_err(),
+            SaveFiError::ReceiptRetention.into()
+        );
+        assert!(receipt.check_retention_elapsed(START + retention).is_ok());
+    }
+
+    #[test]
+    fn reserve_redeems_saved_lamports_one_to_one() {
+        let mut reserve = SavingsReserve { total_saved: 0, bump: 0 };
+        let (reserve_key, delegation_key, user_key) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut reserve_lamports, mut delegation_lamports, mut user_lamports) = (1_000_000, 50_000_000, 0);
+        let (mut reserve_data, mut delegation_data, mut user_data) = ([0u8; 0], [0u8; 0], [0u8; 0]);
+        let reserve_info =
+            AccountInfo::new(&reserve_key, false, true, &mut reserve_lamports, &mut reserve_data, &crate::ID, false, 0);
+        let delegation_info =
+            AccountInfo::new(&delegation_key, false, true, &mut delegation_lamports, &mut delegation_data, &crate::ID, false, 0);
+        let user_info = AccountInfo::new(&user_key, true, true, &mut user_lamports, &mut user_data, &user_key, false, 0);
+
+        reserve.deposit(&reserve_info, &delegation_info, 5_000_000).unwrap();
+        assert_eq!(reserve.total_saved, 5_000_000);
+        assert_eq!(reserve_info.lamports(), 6_000_000);
+        assert_eq!(delegation_info.lamports(), 45_000_000);
+
+        reserve.redeem(&reserve_info, &user_info, 3_000_000).unwrap();
+        assert_eq!(user_info.lamports(), 3_000_000);
+        assert_eq!(reserve.total_saved, 2_000_000);
+        assert_eq!(reserve_info.lamports(), 3_000_000);
+        assert_eq!(
+            reserve.redeem(&reserve_info, &user_info, 2_000_001).unwrap_err(),
+            SaveFiError::InsufficientReserve.into()
+        );
+    }
+
+    #[test]
+    fn governance_counts_distinct_member_signatures() {
+        let members: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let outsider = Pubkey::new_unique();
+        let governance = Governance { signers: members.clone(), threshold: 2, bump: 0 };
+        let owner = Pubkey::default();
+        let mut lamports = [0u64; 4];
+        let mut data: [[u8; 0]; 4] = [[]; 4];
+        let [l0, l1, l2, l3] = &mut lamports;
+        let [d0, d1, d2, d3] = &mut data;
+        let first = AccountInfo::new(&members[0], true, false, l0, d0, &owner, false, 0);
+        let second = AccountInfo::new(&members[1], true, false, l1, d1, &owner, false, 0);
+        let unsigned = AccountInfo::new(&members[2], false, false, l2, d2, &owner, false, 0);
+        let stranger = AccountInfo::new(&outsider, true, false, l3, d3, &owner, false, 0);
+
+        // Exactly the threshold
+        assert!(governance.verify_approvals(&first, std::slice::from_ref(&second)).is_ok());
+        // The same signer listed twice counts once
+        assert_eq!(
+            governance.verify_approvals(&first, &[first.clone(), first.clone()]).unwrap_err(),
+            SaveFiError::InsufficientApprovals.into()
+        );
+        // A member passed without signing does not count
+        assert!(governance.verify_approvals(&first, std::slice::from_ref(&unsigned)).is_err());
+        // A signer outside the governance set does not count
+        assert!(governance.verify_approvals(&stranger, std::slice::from_ref(&first)).is_err());
+        assert!(governance.verify_approvals(&stranger, &[first.clone(), second.clone()]).is_ok());
+    }
+
+    #[test]
+    fn daily_spend_stops_at_the_limit() {
+        let mut delegation = delegation();
+        delegation.daily_limit = 1_000;
+        delegation.record_spend(600, START, 5_000).unwrap();
+        delegation.record_spend(400, START + 60, 5_000).unwrap();
+        assert_eq!(delegation.window_spent, 1_000);
+        assert_eq!(
+            delegation.record_spend(1, START + 120, 5_000).unwrap_err(),
+            SaveFiError::DailyLimitExceeded.into()
+        );
+        assert_eq!(delegation.window_spent, 1_000);
+    }
+
+    #[test]
+    fn daily_spend_window_resets_after_24_hours() {
+        let mut delegation = delegation();
+        delegation.daily_limit = 1_000;
+        delegation.record_spend(1_000, START, 5_000).unwrap();
+        assert!(delegation.record_spend(1, START + DAY - 1, 5_000).is_err());
+        delegation.record_spend(1_000, START + DAY, 5_000).unwrap();
+        assert_eq!(delegation.window_start, START + DAY);
+        assert_eq!(delegation.window_spent, 1_000);
+    }
+
+    #[test]
+    fn personal_limit_never_exceeds_the_protocol_limit() {
+        let mut delegation = delegation();
+        // Zero falls back to the protocol limit
+        assert_eq!(delegation.effective_daily_limit(5_000), 5_000);
+        delegation.record_spend(5_000, START, 5_000).unwrap();
+        assert!(delegation.record_spend(1, START, 5_000).is_err());
+
+        delegation.daily_limit = 10_000;
+        assert_eq!(delegation.effective_daily_limit(5_000), 5_000);
+        delegation.daily_limit = 2_000;
+        assert_eq!(delegation.effective_daily_limit(5_000), 2_000);
+    }
+
+    #[test]
+    fn deductions_wait_out_the_cooldown() {
+        let mut delegation = delegation();
+        delegation.cooldown_secs = 2 * Delegation::default_cooldown();
+        let cooldown = delegation.effective_cooldown();
+        delegation.record_deduction(START).unwrap();
+        assert_eq!(
+            delegation.record_deduction(START + cooldown - 1).unwrap_err(),
+            SaveFiError::DeductionCooldown.into()
+        );
+        assert_eq!(delegation.last_deduction_at, START);
+        delegation.record_deduction(START + cooldown).unwrap();
+        assert_eq!(delegation.last_deduction_at, START + cooldown);
+    }
+
+    #[test]
+    fn zero_cooldown_uses_the_protocol_default() {
+        let mut delegation = delegation();
+        delegation.cooldown_secs = 0;
+        let cooldown = Delegation::default_cooldown();
+        assert_eq!(delegation.effective_cooldown(), cooldown);
+        delegation.record_deduction(START).unwrap();
+        assert!(delegation.record_deduction(START).is_err());
+        assert!(delegation.record_deduction(START + cooldown - 1).is_err());
+        delegation.record_deduction(START + cooldown).unwrap();
+    }
+
+    #[test]
+    fn allowlist_matches_the_destination_or_its_owner() {
+        let mut delegation = delegation();
+        let (key, owner, other) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut lamports, mut data) = (0u64, Vec::<u8>::new());
+        let destination = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &owner, false, 0);
+
+        // An empty allowlist leaves the destination unrestricted
+        delegation.check_destination(&destination).unwrap();
+
+        delegation.allowed_destinations = vec![other];
+        assert_eq!(
+            delegation.check_destination(&destination).unwrap_err(),
+            SaveFiError::DestinationNotAllowed.into()
+        );
+        delegation.allowed_destinations = vec![other, key];
+        delegation.check_destination(&destination).unwrap();
+        delegation.allowed_destinations = vec![owner];
+        delegation.check_destination(&destination).unwrap();
+    }
+
+    #[test]
+    fn dex_only_requires_an_enabled_registered_dex() {
+        let mut delegation = delegation();
+        let mut dex = DexProgram { program_id: Pubkey::new_unique(), enabled: true, updated_at: START, bump: 255 };
+        // Outside DEX-only mode the registry is not consulted
+        delegation.check_dex_program(None).unwrap();
+
+        delegation.dex_only = true;
+        delegation.check_dex_program(Some(&dex)).unwrap();
+        dex.enabled = false;
+        assert_eq!(
+            delegation.check_dex_program(Some(&dex)).unwrap_err(),
+            SaveFiError::DexDisabled.into()
+        );
+        // A removed DEX leaves no registry account to pass in
+        assert_eq!(
+            delegation.check_dex_program(None).unwrap_err(),
+            SaveFiError::DexNotRegistered.into()
+        );
+    }
+
+    #[test]
+    fn param_updates_are_validated() {
+        let config = config();
+        let fee_account = fee_account(config.limits.max_fee_rate);
+        let invalid = [
+            (ParamUpdate::FeeRate(config.limits.max_fee_rate + 1), SaveFiError::InvalidFeeRate),
+            (ParamUpdate::SubscriptionFee(constants::MAX_SUBSCRIPTION_FEE_SOL + 1), SaveFiError::InvalidSubscriptionFee),
+            (ParamUpdate::DelegationLimits { min: 0, max: 1 }, SaveFiError::InvalidDelegationLimits),
+            (ParamUpdate::DelegationLimits { min: 2, max: 1 }, SaveFiError::InvalidDelegationLimits),
+            (
+                ParamUpdate::DelegationLimits { min: 1, max: constants::DELEGATION_LIMIT_CAP_SOL + 1 },
+                SaveFiError::InvalidDelegationLimits,
+            ),
+            (ParamUpdate::TimelockDelay(constants::MIN_TIMELOCK_DELAY_SECS - 1), SaveFiError::InvalidTimelockDelay),
+            (ParamUpdate::TimelockDelay(constants::MAX_TIMELOCK_DELAY_SECS + 1), SaveFiError::InvalidTimelockDelay),
+        ];
+        for (update, error) in invalid {
+            assert_eq!(update.validate(&config, &fee_account).unwrap_err(), error.into(), "{:?}", update);
+        }
+
+        let valid = [
+            ParamUpdate::FeeRate(config.limits.max_fee_rate),
+            ParamUpdate::SubscriptionFee(constants::MAX_SUBSCRIPTION_FEE_SOL),
+            ParamUpdate::DelegationLimits { min: 1, max: constants::DELEGATION_LIMIT_CAP_SOL },
+            ParamUpdate::TimelockDelay(constants::MIN_TIMELOCK_DELAY_SECS),
+            ParamUpdate::TimelockDelay(constants::MAX_TIMELOCK_DELAY_SECS),
+        ];
+        for update in valid {
+            assert!(update.validate(&config, &fee_account).is_ok(), "{:?}", update);
+        }
+    }
+
+    #[test]
+    fn param_changes_execute_between_eta_and_the_grace_period() {
+        let change = ParamChange {
+            id: 0,
+            update: ParamUpdate::SubscriptionFee(1),
+            eta: START + DAY,
+            proposer: Pubkey::new_unique(),
+            bump: 255,
+        };
+        assert_eq!(change.check_executable(START).unwrap_err(), SaveFiError::TimelockNotElapsed.into());
+        assert_eq!(change.check_executable(change.eta - 1).unwrap_err(), SaveFiError::TimelockNotElapsed.into());
+        change.check_executable(change.eta).unwrap();
+        let deadline = change.eta + constants::TIMELOCK_GRACE_PERIOD_SECS;
+        change.check_executable(deadline).unwrap();
+        assert_eq!(change.check_executable(deadline + 1).unwrap_err(), SaveFiError::ParamChangeStale.into());
+    }
+
+    #[test]
+    fn closed_param_changes_cannot_be_executed() {
+        let change = ParamChange {
+            id: 7,
+            update: ParamUpdate::SubscriptionFee(1),
+            eta: START,
+            proposer: Pubkey::new_unique(),
+            bump: 255,
+        };
+        let (key, _) = Pubkey::find_program_address(&[b"param_change", change.id.to_le_bytes().as_ref()], &crate::ID);
+        let mut data = Vec::new();
+        change.try_serialize(&mut data).unwrap();
+        let mut lamports = 1_000_000;
+        let queued = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &crate::ID, false, 0);
+        assert!(Account::<ParamChange>::try_from(&queued).is_ok());
+
+        // Cancelling or executing closes the account; this is what the runtime leaves
+        // behind, so a cancelled change and a second execute both fail to load it
+        let system_program = anchor_lang::system_program::ID;
+        let (mut lamports, mut data) = (0u64, Vec::<u8>::new());
+        let closed = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &system_program, false, 0);
+        assert_eq!(
+            Account::<ParamChange>::try_from(&closed).err(),
+            Some(ErrorCode::AccountNotInitialized.into())
+        );
+    }
+
+    fn token_2022_mint(fee_config: Option<TransferFeeConfig>, default_account_state: bool) -> Vec<u8> {
+        use spl_token_2022::extension::{default_account_state::DefaultAccountState, BaseStateWithExtensionsMut, StateWithExtensionsMut};
+        let mut extensions = Vec::new();
+        if fee_config.is_some() {
+            extensions.push(ExtensionType::TransferFeeConfig);
+        }
+        if default_account_state {
+            extensions.push(ExtensionType::DefaultAccountState);
+        }
+        let len = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&extensions).unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data).unwrap();
+        state.base = spl_token_2022::state::Mint { decimals: 6, is_initialized: true, ..Default::default() };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        if let Some(fee_config) = fee_config {
+            *state.init_extension::<TransferFeeConfig>(true).unwrap() = fee_config;
+        }
+        if default_account_state {
+            state.init_extension::<DefaultAccountState>(true).unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn transfer_fees_follow_the_mint_config() {
+        // 1% from epoch 5, capped at 50 base units; no fee before that
+        let mut fee_config = TransferFeeConfig::default();
+        fee_config.newer_transfer_fee.epoch = 5.into();
+        fee_config.newer_transfer_fee.transfer_fee_basis_points = 100.into();
+        fee_config.newer_transfer_fee.maximum_fee = 50.into();
+        let key = Pubkey::new_unique();
+        let mut data = token_2022_mint(Some(fee_config), false);
+        let mut lamports = 0;
+        let mint = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &spl_token_2022::ID, false, 0);
+
+        assert_eq!(transfer_fee(&mint, 1_000, 4).unwrap(), 0);
+        assert_eq!(with_transfer_fee(&mint, 1_000, 4).unwrap(), 1_000);
+        assert_eq!(transfer_fee(&mint, 1_000, 5).unwrap(), 10);
+        let sent = with_transfer_fee(&mint, 1_000, 5).unwrap();
+        assert_eq!(sent - transfer_fee(&mint, sent, 5).unwrap(), 1_000);
+        assert_eq!(with_transfer_fee(&mint, 0, 5).unwrap(), 0);
+
+        // Past 5,000 base units the fee is clamped to `maximum_fee`
+        assert_eq!(transfer_fee(&mint, 1_000_000, 5).unwrap(), 50);
+        assert_eq!(with_transfer_fee(&mint, 1_000_000, 5).unwrap(), 1_000_050);
+
+        // Legacy SPL mints never charge a transfer fee
+        let (mut lamports, mut data) = (0, Vec::<u8>::new());
+        let legacy = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &anchor_spl::token::ID, false, 0);
+        assert_eq!(transfer_fee(&legacy, 1_000_000, 5).unwrap(), 0);
+        assert_eq!(with_transfer_fee(&legacy, 1_000_000, 5).unwrap(), 1_000_000);
+    }
+
+    #[test]
+    fn mints_with_a_default_account_state_are_rejected() {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = token_2022_mint(Some(TransferFeeConfig::default()), false);
+        let mint = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &spl_token_2022::ID, false, 0);
+        check_mint_extensions(&mint).unwrap();
+
+        let mut lamports = 0;
+        let mut data = token_2022_mint(None, true);
+        let mint = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &spl_token_2022::ID, false, 0);
+        assert_eq!(check_mint_extensions(&mint).unwrap_err(), SaveFiError::UnsupportedMintExtension.into());
+    }
+
+    #[test]
+    fn timestamp_hints_must_be_recent() {
+        let (skew, staleness) = (constants::MAX_CLOCK_SKEW_SECS, constants::MAX_TIMESTAMP_STALENESS_SECS);
+        check_timestamp_hint(None, START).unwrap();
+        check_timestamp_hint(Some(START), START).unwrap();
+        check_timestamp_hint(Some(START + skew), START).unwrap();
+        check_timestamp_hint(Some(START - staleness), START).unwrap();
+
+        assert_eq!(
+            check_timestamp_hint(Some(START + skew + 1), START).unwrap_err(),
+            SaveFiError::InvalidTimestamp.into()
+        );
+        assert_eq!(
+            check_timestamp_hint(Some(START - staleness - 1), START).unwrap_err(),
+            SaveFiError::StaleTimestamp.into()
+        );
+        assert_eq!(check_timestamp_hint(Some(i64::MIN), START).unwrap_err(), SaveFiError::StaleTimestamp.into());
+        assert_eq!(check_timestamp_hint(Some(i64::MAX), START).unwrap_err(), SaveFiError::InvalidTimestamp.into());
+    }
+
+    #[test]
+    fn mint_limits_only_loosen_through_the_timelock() {
+        let mut mint_config = MintConfig {
+            mint: Pubkey::default(),
+            min_delegation: 0,
+            max_delegation: 0,
+            daily_limit: 0,
+            enabled: false,
+            bump: 255,
+        };
+        // A mint being configured for the first time takes any valid limits
+        mint_config.check_immediate_update(10, 1_000, 5_000).unwrap();
+        assert_eq!(
+            MintConfig::check_limits(10, 1_000, 500).unwrap_err(),
+            SaveFiError::InvalidDelegationLimits.into()
+        );
+
+        mint_config.mint = Pubkey::new_unique();
+        mint_config.min_delegation = 10;
+        mint_config.max_delegation = 1_000;
+        mint_config.daily_limit = 5_000;
+        mint_config.check_immediate_update(10, 1_000, 5_000).unwrap();
+        mint_config.check_immediate_update(20, 500, 1_000).unwrap();
+        for (min, max, daily_limit) in [(9, 1_000, 5_000), (10, 1_001, 5_000), (10, 1_000, 5_001)] {
+            assert_eq!(
+                mint_config.check_immediate_update(min, max, daily_limit).unwrap_err(),
+                SaveFiError::LimitChangeRequiresTimelock.into()
+            );
+        }
+
+        let (config, fee_account) = (config(), fee_account(1));
+        let raise = ParamUpdate::MintLimits { mint: mint_config.mint, min: 10, max: 2_000, daily_limit: 10_000 };
+        raise.validate(&config, &fee_account).unwrap();
+        let invalid = ParamUpdate::MintLimits { mint: mint_config.mint, min: 0, max: 2_000, daily_limit: 10_000 };
+        assert_eq!(
+            invalid.validate(&config, &fee_account).unwrap_err(),
+            SaveFiError::InvalidDelegationLimits.into()
+        );
+    }
+}
@@ -2,8 +2,17 @@ import * as anchor from "@project-serum/anchor";
 import { Program, Idl } from "@project-serum/anchor";
 import { Savefi } from "../target/types/savefi";
 import { PublicKey, SystemProgram, LAMPORTS_PER_SOL } from "@solana/web3.js";
-import { TOKEN_PROGRAM_ID, ASSOCIATED_TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import {
+  TOKEN_PROGRAM_ID,
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  createMint,
+  createAssociatedTokenAccount,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  mintTo,
+} from "@solana/spl-token";
 import { expect } from "chai";
+import { randomBytes } from "crypto";
 
 describe("savefi", () => {
   const provider = anchor.AnchorProvider.env();
@@ -104,7 +113,7 @@ describe("savefi", () => {
   it("Fails to withdraw before lock period", async () => {
     try {
       await program.methods
-        .withdraw()
+        .withdraw(null)
         .accounts({
           vault: vaultPda,
           user: wallet.publicKey,
@@ -122,4 +131,440 @@ describe("savefi", () => {
 
   // Note: Testing the transfer hook would require additional setup with SPL Token 2022
   // and is more complex to test in this environment
-}); 
\ No newline at end of file
+}); 
+// The protocol accounts are global PDAs, so the suites below share one setup:
+// whichever runs first initializes the protocol with the provider wallet as admin.
+const findPda = (program: Program<Idl>, ...seeds: Buffer[]) =>
+  PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+
+async function ensureProtocol(program: Program<Idl>, provider: anchor.AnchorProvider): Promise<PublicKey> {
+  const wallet = provider.wallet as anchor.Wallet;
+  const configPda = findPda(program, Buffer.from("config"));
+  if (!(await provider.connection.getAccountInfo(configPda))) {
+    const mintAuthority = findPda(program, Buffer.from("mint_authority"));
+    const mint = await createMint(provider.connection, wallet.payer, mintAuthority, null, 9);
+    await program.methods
+      .initializeMints(1)
+      .accounts({
+        mintAuthority,
+        feeAccount: findPda(program, Buffer.from("fee_account")),
+        config: configPda,
+        reentrancyGuard: findPda(program, Buffer.from("reentrancy_guard")),
+        reserve: findPda(program, Buffer.from("reserve")),
+        saveTokenMint: mint,
+        admin: wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+  }
+  const config = await program.account.protocolConfig.fetch(configPda);
+  expect((config.admin as PublicKey).toBase58()).to.equal(wallet.publicKey.toBase58());
+  return config.saveTokenMint as PublicKey;
+}
+
+// Funds `user` and opens their vault with a 10% savings rate and a 7 day lock.
+async function setupUser(
+  program: Program<Idl>,
+  provider: anchor.AnchorProvider,
+  user: anchor.web3.Keypair,
+  saveTokenMint: PublicKey
+): Promise<void> {
+  const airdrop = await provider.connection.requestAirdrop(user.publicKey, 2 * LAMPORTS_PER_SOL);
+  await provider.connection.confirmTransaction(airdrop);
+
+  const vaultPda = findPda(program, Buffer.from("vault"), user.publicKey.toBuffer());
+  await program.methods
+    .initializeVault(10, 7)
+    .accounts({
+      vault: vaultPda,
+      proxyAccount: findPda(program, Buffer.from("proxy"), user.publicKey.toBuffer()),
+      vaultTokenAccount: getAssociatedTokenAddressSync(saveTokenMint, vaultPda, true),
+      saveTokenMint,
+      user: user.publicKey,
+      config: findPda(program, Buffer.from("config")),
+      systemProgram: SystemProgram.programId,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+    })
+    .signers([user])
+    .rpc();
+}
+
+describe("auto_deduct", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Savefi as Program<Idl>;
+  const user = anchor.web3.Keypair.generate();
+  const destination = anchor.web3.Keypair.generate();
+  const delegationId = 0;
+
+  const pda = (...seeds: Buffer[]) => findPda(program, ...seeds);
+
+  const mintAuthorityPda = pda(Buffer.from("mint_authority"));
+  const feeAccountPda = pda(Buffer.from("fee_account"));
+  const configPda = pda(Buffer.from("config"));
+  const reentrancyGuardPda = pda(Buffer.from("reentrancy_guard"));
+  const reservePda = pda(Buffer.from("reserve"));
+  const vaultPda = pda(Buffer.from("vault"), user.publicKey.toBuffer());
+  const proxyPda = pda(Buffer.from("proxy"), user.publicKey.toBuffer());
+  const delegationPda = pda(
+    Buffer.from("delegation"),
+    user.publicKey.toBuffer(),
+    Buffer.from([delegationId])
+  );
+  const delegationRegistryPda = pda(Buffer.from("delegations"), user.publicKey.toBuffer());
+
+  let saveTokenMint: PublicKey;
+  let vaultTokenAccount: PublicKey;
+
+  const receiptPda = (tradeId: Buffer, delegation: PublicKey = delegationPda) =>
+    pda(Buffer.from("receipt"), delegation.toBuffer(), tradeId);
+
+  const autoDeduct = (
+    timestamp: anchor.BN | null,
+    tradeId: Buffer = randomBytes(16),
+    operator: anchor.web3.Keypair = user,
+    delegation: PublicKey = delegationPda,
+    dexProgram: PublicKey = program.programId
+  ) => {
+    return program.methods
+      .autoDeduct([...tradeId], new anchor.BN(0.01 * LAMPORTS_PER_SOL), timestamp)
+      .accounts({
+        vault: vaultPda,
+        proxyAccount: proxyPda,
+        vaultTokenAccount,
+        saveTokenMint,
+        mintAuthority: mintAuthorityPda,
+        feeAccount: feeAccountPda,
+        reserve: reservePda,
+        delegation,
+        operator: operator.publicKey,
+        tradeReceipt: receiptPda(tradeId, delegation),
+        destination: destination.publicKey,
+        dexProgram,
+        config: configPda,
+        reentrancyGuard: reentrancyGuardPda,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([operator])
+      .rpc();
+  };
+
+  before(async () => {
+    saveTokenMint = await ensureProtocol(program, provider);
+    vaultTokenAccount = getAssociatedTokenAddressSync(saveTokenMint, vaultPda, true);
+    await setupUser(program, provider, user, saveTokenMint);
+
+    await program.methods
+      .delegateFunds(delegationId, new anchor.BN(0.5 * LAMPORTS_PER_SOL), 7)
+      .accounts({
+        delegation: delegationPda,
+        delegationRegistry: delegationRegistryPda,
+        user: user.publicKey,
+        config: configPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  });
+
+  it("Rejects a forged timestamp from the past", async () => {
+    try {
+      await autoDeduct(new anchor.BN(0));
+      expect.fail("Should have thrown an error");
+    } catch (err) {
+      expect(err.toString()).to.include("StaleTimestamp");
+    }
+  });
+
+  it("Rejects a forged timestamp from the future", async () => {
+    const slot = await provider.connection.getSlot();
+    const now = (await provider.connection.getBlockTime(slot)) ?? Math.floor(Date.now() / 1000);
+    try {
+      await autoDeduct(new anchor.BN(now + 24 * 60 * 60));
+      expect.fail("Should have thrown an error");
+    } catch (err) {
+      expect(err.toString()).to.include("InvalidTimestamp");
+    }
+  });
+
+  // Deductions are rate limited per delegation, so the tests below share one trade
+  const tradeId = randomBytes(16);
+
+  it("Moves the saved share of a deduction into the reserve", async () => {
+    const lamportsBefore = await provider.connection.getBalance(reservePda);
+    const savedBefore = (await program.account.savingsReserve.fetch(reservePda)).totalSaved.toNumber();
+
+    await autoDeduct(null, tradeId);
+
+    // 10% savings rate on a 0.01 SOL trade
+    const saved = 0.001 * LAMPORTS_PER_SOL;
+    expect(await provider.connection.getBalance(reservePda)).to.equal(lamportsBefore + saved);
+    const reserve = await program.account.savingsReserve.fetch(reservePda);
+    expect(reserve.totalSaved.toNumber()).to.equal(savedBefore + saved);
+    const receipt = await program.account.tradeReceipt.fetch(receiptPda(tradeId));
+    expect(receipt.tradeAmount.toNumber()).to.equal(0.01 * LAMPORTS_PER_SOL);
+    expect(receipt.payer.toBase58()).to.equal(user.publicKey.toBase58());
+  });
+
+  it("Rejects a replayed trade id", async () => {
+    try {
+      await autoDeduct(null, tradeId);
+      expect.fail("Should have thrown an error");
+    } catch (err) {
+      expect(err.toString()).to.include("DuplicateTrade");
+    }
+  });
+
+  // The retention window is 24 hours and the local validator clock cannot be
+  // warped, so closing after retention is covered by the program's unit tests.
+  it("Keeps a trade receipt open during the retention window", async () => {
+    try {
+      await program.methods
+        .closeTradeReceipt()
+        .accounts({ tradeReceipt: receiptPda(tradeId), payer: user.publicKey })
+        .signers([user])
+        .rpc();
+      expect.fail("Should have thrown an error");
+    } catch (err) {
+      expect(err.toString()).to.include("ReceiptRetention");
+    }
+  });
+
+  it("Only lets the delegation's operator deduct", async () => {
+    const operator = anchor.web3.Keypair.generate();
+    const airdrop = await provider.connection.requestAirdrop(operator.publicKey, LAMPORTS_PER_SOL);
+    await provider.connection.confirmTransaction(airdrop);
+
+    try {
+      await autoDeduct(null, randomBytes(16), operator);
+      expect.fail("Should have thrown an error");
+    } catch (err) {
+      expect(err.toString()).to.include("UnauthorizedOperator");
+    }
+
+    await program.methods
+      .setOperator(operator.publicKey)
+      .accounts({ delegation: delegationPda, user: user.publicKey })
+      .signers([user])
+      .rpc();
+    const delegation = await program.account.delegation.fetch(delegationPda);
+    expect((delegation.operator as PublicKey).toBase58()).to.equal(operator.publicKey.toBase58());
+
+    // The owner is no longer the operator
+    try {
+      await autoDeduct(null);
+      expect.fail("Should have thrown an error");
+    } catch (err) {
+      expect(err.toString()).to.include("UnauthorizedOperator");
+    }
+
+    // The new operator gets past the signer check and hits the cooldown
+    // left by the deduction above
+    try {
+      await autoDeduct(null, randomBytes(16), operator);
+      expect.fail("Should have thrown an error");
+    } catch (err) {
+      expect(err.toString()).to.include("DeductionCooldown");
+    }
+  });
+
+  it("Rejects a DEX-only deduction through a disabled DEX", async () => {
+    const wallet = provider.wallet as anchor.Wallet;
+    // A fresh destination keypair is owned by the system program, so that is the DEX here
+    const dexPda = pda(Buffer.from("dex"), SystemProgram.programId.toBuffer());
+    const dexDelegationPda = pda(Buffer.from("delegation"), user.publicKey.toBuffer(), Buffer.from([1]));
+
+    await program.methods
+      .delegateFunds(1, new anchor.BN(0.5 * LAMPORTS_PER_SOL), 7)
+      .accounts({
+        delegation: dexDelegationPda,
+        delegationRegistry: delegationRegistryPda,
+        user: user.publicKey,
+        config: configPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+    await program.methods
+      .setDexOnly(true)
+      .accounts({ delegation: dexDelegationPda, user: user.publicKey })
+      .signers([user])
+      .rpc();
+
+    if (!(await provider.connection.getAccountInfo(dexPda))) {
+      await program.methods
+        .addDexProgram()
+        .accounts({
+          dexProgram: dexPda,
+          program: SystemProgram.programId,
+          config: configPda,
+          admin: wallet.publicKey,
+          governance: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+    await program.methods
+      .setDexProgramEnabled(false)
+      .accounts({ dexProgram: dexPda, config: configPda, admin: wallet.publicKey, governance: null })
+      .rpc();
+
+    try {
+      await autoDeduct(null, randomBytes(16), user, dexDelegationPda, dexPda);
+      expect.fail("Should have thrown an error");
+    } catch (err) {
+      expect(err.toString()).to.include("DexDisabled");
+    }
+  });
+});
+
+describe("token delegations", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Savefi as Program<Idl>;
+  const wallet = provider.wallet as anchor.Wallet;
+  const user = anchor.web3.Keypair.generate();
+  const destination = anchor.web3.Keypair.generate();
+
+  const pda = (...seeds: Buffer[]) => findPda(program, ...seeds);
+
+  const feeAccountPda = pda(Buffer.from("fee_account"));
+  const configPda = pda(Buffer.from("config"));
+  const vaultPda = pda(Buffer.from("vault"), user.publicKey.toBuffer());
+  const delegationPda = (id: number) =>
+    pda(Buffer.from("delegation"), user.publicKey.toBuffer(), Buffer.from([id]));
+  const delegationRegistryPda = pda(Buffer.from("delegations"), user.publicKey.toBuffer());
+
+  let usdc: PublicKey;
+  let userUsdc: PublicKey;
+  let vaultUsdc: PublicKey;
+  let feeUsdc: PublicKey;
+  let tokenSavingsPda: PublicKey;
+
+  const delegateTokens = (id: number, amount: number) =>
+    program.methods
+      .delegateTokens(id, new anchor.BN(amount), 7)
+      .accounts({
+        delegation: delegationPda(id),
+        delegationRegistry: delegationRegistryPda,
+        mint: usdc,
+        mintConfig: pda(Buffer.from("mint_config"), usdc.toBuffer()),
+        escrow: getAssociatedTokenAddressSync(usdc, delegationPda(id), true),
+        userTokenAccount: userUsdc,
+        vault: vaultPda,
+        tokenSavings: tokenSavingsPda,
+        vaultTokenAccount: vaultUsdc,
+        user: user.publicKey,
+        config: configPda,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      })
+      .signers([user])
+      .rpc();
+
+  const balance = async (account: PublicKey) =>
+    Number((await getAccount(provider.connection, account)).amount);
+
+  before(async () => {
+    await setupUser(program, provider, user, await ensureProtocol(program, provider));
+
+    usdc = await createMint(provider.connection, wallet.payer, wallet.publicKey, null, 6);
+    userUsdc = await createAssociatedTokenAccount(provider.connection, wallet.payer, usdc, user.publicKey);
+    await mintTo(provider.connection, wallet.payer, usdc, userUsdc, wallet.payer, 1_000_000_000);
+    vaultUsdc = getAssociatedTokenAddressSync(usdc, vaultPda, true);
+    feeUsdc = getAssociatedTokenAddressSync(usdc, feeAccountPda, true);
+    tokenSavingsPda = pda(Buffer.from("token_savings"), vaultPda.toBuffer(), usdc.toBuffer());
+
+    await program.methods
+      .setMintConfig(new anchor.BN(1_000_000), new anchor.BN(500_000_000), new anchor.BN(1_000_000_000), true)
+      .accounts({
+        mintConfig: pda(Buffer.from("mint_config"), usdc.toBuffer()),
+        mint: usdc,
+        feeAccount: feeAccountPda,
+        feeTokenAccount: feeUsdc,
+        config: configPda,
+        admin: wallet.publicKey,
+        governance: null,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+  });
+
+  it("Escrows tokens and refunds them on revoke", async () => {
+    const escrow = getAssociatedTokenAddressSync(usdc, delegationPda(0), true);
+    await delegateTokens(0, 100_000_000);
+    expect(await balance(escrow)).to.equal(100_000_000);
+
+    await program.methods
+      .revokeTokenDelegation()
+      .accounts({
+        delegation: delegationPda(0),
+        mint: usdc,
+        escrow,
+        userTokenAccount: userUsdc,
+        user: user.publicKey,
+        config: configPda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([user])
+      .rpc();
+    expect(await balance(userUsdc)).to.equal(1_000_000_000);
+    expect(await provider.connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it("Splits a token deduction between savings, fees and the destination", async () => {
+    const escrow = getAssociatedTokenAddressSync(usdc, delegationPda(1), true);
+    const destinationUsdc = await createAssociatedTokenAccount(
+      provider.connection,
+      wallet.payer,
+      usdc,
+      destination.publicKey
+    );
+    await delegateTokens(1, 100_000_000);
+    const vaultBefore = await balance(vaultUsdc);
+    const feesBefore = await balance(feeUsdc);
+
+    const tradeId = randomBytes(16);
+    const tradeAmount = 10_000_000;
+    await program.methods
+      .autoDeductTokens([...tradeId], new anchor.BN(tradeAmount), null)
+      .accounts({
+        vault: vaultPda,
+        tokenSavings: tokenSavingsPda,
+        vaultTokenAccount: vaultUsdc,
+        feeAccount: feeAccountPda,
+        feeTokenAccount: feeUsdc,
+        delegation: delegationPda(1),
+        escrow,
+        mint: usdc,
+        mintConfig: pda(Buffer.from("mint_config"), usdc.toBuffer()),
+        operator: user.publicKey,
+        tradeReceipt: pda(Buffer.from("receipt"), delegationPda(1).toBuffer(), tradeId),
+        destination: destinationUsdc,
+        config: configPda,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([user])
+      .rpc();
+
+    const { feeRate } = await program.account.feeAccount.fetch(feeAccountPda);
+    const saved = tradeAmount / 10;
+    const fee = (tradeAmount * (feeRate as number)) / 100;
+    expect(await balance(vaultUsdc)).to.equal(vaultBefore + saved);
+    expect(await balance(feeUsdc)).to.equal(feesBefore + fee);
+    expect(await balance(destinationUsdc)).to.equal(tradeAmount - saved - fee);
+    expect(await balance(escrow)).to.equal(100_000_000 - tradeAmount);
+
+    const savings = await program.account.tokenSavings.fetch(tokenSavingsPda);
+    expect(savings.balance.toNumber()).to.equal(saved);
+  });
+});
@@ -1,4 +1,4 @@
-import { AnchorProvider, Program, web3, Idl } from '@project-serum/anchor';
+import { AnchorProvider, Program, web3, Idl, BN } from '@project-serum/anchor';
 import { useWallet, useAnchorWallet, useConnection } from '@solana/wallet-adapter-react';
 import { Connection, PublicKey, SystemProgram, TransactionSignature } from '@solana/web3.js';
 import { useState, useEffect, useMemo } from 'react';
@@ -110,7 +110,6 @@ export async function initializeVault(
         saveTokenMint: SAVESOL_MINT,
         user,
         config: CONFIG_PDA,
-        admin: ADMIN_PUBKEY,
         systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
@@ -180,21 +179,36 @@ export const getVaultData = async (program: Program, vaultPDA: PublicKey) => {
   }
 };
 
-// Withdraw from vault
+// Get locked vs unlocked SaveSOL in a vault
+export const getVaultBalances = async (program: Program, vaultPDA: PublicKey) => {
+  try {
+    const balances = await program.methods
+      .vaultBalances()
+      .accounts({ vault: vaultPDA })
+      .view();
+    return { success: true, balances };
+  } catch (error) {
+    console.error('Error fetching vault balances:', error);
+    return { success: false, error };
+  }
+};
+
+// Withdraw from vault; omit the amount to withdraw the whole unlocked balance
 export async function withdraw(
   program: Program<Idl>,
-  vaultPDA: PublicKey
+  vaultPDA: PublicKey,
+  amount?: number
 ): Promise<{ success: boolean }> {
   try {
     const tx = await program.methods
-      .withdraw()
+      .withdraw(amount === undefined ? null : new BN(amount))
       .accounts({
         vault: vaultPDA,
+        reserve: PublicKey.findProgramAddressSync([Buffer.from('reserve')], program.programId)[0],
         user: program.provider.publicKey,
         vaultTokenAccount: await getAssociatedTokenAddress(program.provider.publicKey, SAVESOL_MINT),
         saveTokenMint: SAVESOL_MINT,
         config: CONFIG_PDA,
-        admin: ADMIN_PUBKEY,
         tokenProgram: TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
       })
@@ -339,7 +353,6 @@ export async function updateVault(
         vault: vaultPDA,
         user: program.provider.publicKey,
         config: CONFIG_PDA,
-        admin: ADMIN_PUBKEY,
       })
       .rpc();
     return { success: true };